@@ -1,16 +1,41 @@
 pub const PAGE_LOGIC: &str = r#"
 <script type="text/javascript">
 addEventListener("load", (event)=>{
-    const current_page = new URLSearchParams(window.location.search).get("page") ?? "home";
+    const default_page = document.body.dataset.defaultPage ?? "home";
+    const current_page = new URLSearchParams(window.location.search).get("page") ?? default_page;
     const current_page_id = "page-" + current_page;
     document.getElementById(current_page_id).style.visibility = "visible";
+});
+</script>
+"#;
 
-    let date_elems = document.getElementsByClassName("blog-post-date");
-    for(let date_elem of date_elems){
-        const utc = Number(date_elem.innerHTML);
-        const utcString = new Date(utc).toLocaleString();
-        date_elem.innerHTML = utcString;
+pub const DATE_LOGIC: &str = r#"
+<script type="text/javascript">
+addEventListener("load", (event)=>{
+    for(let date_elem of document.querySelectorAll("time.blog-post-date")){
+        date_elem.textContent = new Date(date_elem.dateTime).toLocaleString();
     }
 });
 </script>
 "#;
+
+pub const LIVE_RELOAD: &str = r#"
+<script type="text/javascript">
+(() => {
+    const events = new EventSource("/__nr_cms/events");
+    events.addEventListener("reload", () => window.location.reload());
+    events.addEventListener("build-error", (event) => {
+        let overlay = document.getElementById("nr-cms-error-overlay");
+        if (!overlay) {
+            overlay = document.createElement("pre");
+            overlay.id = "nr-cms-error-overlay";
+            overlay.style.cssText = "position:fixed;inset:0;margin:0;padding:2em;overflow:auto;"
+                + "background:rgba(0,0,0,0.85);color:#ff8080;font-size:14px;z-index:2147483647;"
+                + "white-space:pre-wrap;";
+            document.body.appendChild(overlay);
+        }
+        overlay.textContent = "Build failed:\n\n" + event.data;
+    });
+})();
+</script>
+"#;
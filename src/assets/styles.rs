@@ -16,12 +16,89 @@ body{
     flex-direction: column;
     align-items: center;
 }
+.page.current-page{
+    visibility: visible;
+}
+.image-left{
+    align-self: flex-start;
+}
+.image-right{
+    align-self: flex-end;
+}
+figure.image{
+    display: flex;
+    flex-direction: column;
+    align-items: center;
+}
+figcaption{
+    font-size: small;
+    color: grey;
+}
+.blog-post h2 a{
+    color: inherit;
+    text-decoration: none;
+}
 .blog-post h2{
     margin-bottom: 0.25em;
 }
-.blog-post-date{
+.blog-post-date, .blog-post-updated, .blog-post-author{
     font-size: small;
     color: grey;
 }
+.blog-post-cover img{
+    width: 100%;
+    height: auto;
+}
+.blog-post-tags, .blog-tags{
+    font-size: small;
+}
+.blog-tags{
+    list-style: none;
+    padding: 0;
+    display: flex;
+    flex-wrap: wrap;
+    gap: 0.25em 1em;
+}
+.blog-tag{
+    display: none;
+}
+.blog-tag:target{
+    display: block;
+}
+.blog-pagination{
+    display: flex;
+    gap: 0.5em;
+    justify-content: center;
+}
+.blog-page + .blog-page, .blog-post-details > .blog-post{
+    display: none;
+}
+.blog-page:target, .blog-page:has(:target), .blog-post-details > .blog-post:target{
+    display: block;
+}
+.blog:has(.blog-page:target, .blog-page :target) .blog-page:not(:target):not(:has(:target)),
+.blog:has(.blog-post-details :target, .blog-tag:target) .blog-page{
+    display: none;
+}
+.code-keyword{
+    color: #a626a4;
+    font-weight: bold;
+}
+.code-type{
+    color: #c18401;
+}
+.code-string{
+    color: #50a14f;
+}
+.code-number{
+    color: #986801;
+}
+.code-comment{
+    color: #a0a1a7;
+    font-style: italic;
+}
+.code-variable{
+    color: #e45649;
+}
 </style>
 "#;
@@ -20,3 +20,59 @@ fill="#000000" stroke="none">
 </g>
 </svg>
 "##;
+
+pub static GITLAB_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<path d="M12 22.5 1.5 14.8 4.6 2.5l3.2 8.8h8.4l3.2-8.8 3.1 12.3z"/>
+</svg>
+"##;
+
+pub static CODEBERG_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<path fill-rule="evenodd" d="M12 2 .5 21.5h23zm0 6.5 6.3 10.5H5.7z"/>
+</svg>
+"##;
+
+pub static LINKEDIN_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<path fill-rule="evenodd" d="M4 0h16a4 4 0 0 1 4 4v16a4 4 0 0 1-4 4H4a4 4 0 0 1-4-4V4a4 4 0 0 1 4-4zm1 9v10h3V9zm1.5-4.5a1.75 1.75 0 1 0 0 3.5 1.75 1.75 0 0 0 0-3.5zM10 9v10h3v-5.5c0-1.6 1-2.5 2.3-2.5 1.2 0 1.7.9 1.7 2.3V19h3v-6c0-2.7-1.1-4.3-3.7-4.3-1.5 0-2.7.8-3.3 1.8V9z"/>
+</svg>
+"##;
+
+pub static MASTODON_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<path fill-rule="evenodd" d="M21.5 8c0-4.3-2.8-5.6-2.8-5.6-1.4-.7-3.9-.9-6.7-.9h-.1c-2.8 0-5.3.2-6.7.9 0 0-2.8 1.3-2.8 5.6 0 1 0 2.2.1 3.4.3 4.4 1.6 8.8 6.3 10 2.2.6 4 .7 5.5.6 2.7-.1 4.2-1 4.2-1l-.1-2s-1.9.6-4.1.5c-2.1-.1-4.4-.2-4.7-2.9v-.7s2.1.5 4.7.6c1.6.1 3.1-.1 4.6-.3 2.9-.3 5.4-2.1 5.7-3.7.5-2.5.4-6 .4-6zm-4.1 5.5H15V8.9c0-1-.4-1.5-1.2-1.5-.9 0-1.4.6-1.4 1.8v2.6H10V9.2c0-1.2-.5-1.8-1.4-1.8-.8 0-1.2.5-1.2 1.5v4.6H5V8.8c0-1 .3-1.7.8-2.3.5-.6 1.2-.9 2-.9 1 0 1.7.4 2.2 1.1l.5.8.5-.8c.5-.7 1.2-1.1 2.2-1.1.8 0 1.5.3 2 .9.5.6.8 1.3.8 2.3z"/>
+</svg>
+"##;
+
+pub static EMAIL_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" class="link_image">
+<rect x="2" y="4" width="20" height="16" rx="2"/>
+<path d="m2 6 10 7 10-7"/>
+</svg>
+"##;
+
+pub static BLUESKY_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<path d="M5.2 3.3C7.9 5.3 10.8 9.4 12 11.7c1.2-2.3 4.1-6.4 6.8-8.4 1.9-1.5 5.2-2.6 5.2 1.1 0 .7-.4 6.2-.7 7.1-.9 3.1-4.1 3.9-6.9 3.4 4.9.8 6.2 3.6 3.5 6.4-5.1 5.3-7.4-1.3-7.9-3L12 17.6l-.1.3c-.6 1.7-2.8 8.3-7.9 3-2.7-2.8-1.4-5.6 3.5-6.4-2.8.5-6-.3-6.9-3.4C.4 10.6 0 5.1 0 4.4 0 .7 3.3 1.8 5.2 3.3z"/>
+</svg>
+"##;
+
+pub static STACK_OVERFLOW_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<path d="M3 14h2v5h12v-5h2v7H3zm4 2h8v2H7zm.3-3.4 7.8 1.7-.4 2-7.8-1.7zm1.1-3.7 7.2 3.4-.8 1.8-7.2-3.4zm2.1-3.5 6.1 5.1-1.3 1.5-6.1-5.1zm3.4-3 4.7 6.4-1.6 1.2-4.7-6.4z"/>
+</svg>
+"##;
+
+pub static ORCID_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<path fill-rule="evenodd" d="M12 0a12 12 0 1 0 0 24 12 12 0 0 0 0-24zM7 9.5V18h1.8V9.5zm.9-3.5a1.1 1.1 0 1 0 0 2.2 1.1 1.1 0 0 0 0-2.2zm2.6 3.5V18H14c3.3 0 4.8-2.3 4.8-4.25S17.3 9.5 14 9.5zm1.8 1.6h1.6c2.2 0 3 1.5 3 2.65s-.7 2.65-3 2.65h-1.6z"/>
+</svg>
+"##;
+
+pub static RSS_SVG: &str = r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" fill="currentColor" class="link_image">
+<circle cx="5" cy="19" r="2.5"/>
+<path d="M2.5 10.5v3.2a7.8 7.8 0 0 1 7.8 7.8h3.2A11 11 0 0 0 2.5 10.5zm0-6.3v3.2a14.1 14.1 0 0 1 14.1 14.1h3.2A17.3 17.3 0 0 0 2.5 4.2z"/>
+</svg>
+"##;
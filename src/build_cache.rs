@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::types::{cms_blog::BlogPost, diagnostic::Diagnostic};
+
+/// Directory inside the generation directory that keeps processed images between runs.
+pub const CACHE_DIR: &str = ".nr_cms_cache";
+
+/// Hashes file contents. The hash is only used to detect changes, it is not cryptographic.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The files something was built from, with their hashes at that time.
+/// A file that could not be read is recorded with `None`.
+pub type Dependencies = Vec<(PathBuf, Option<u64>)>;
+
+/// A source file as it was when it was last hashed.
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+    hash: u64,
+}
+
+/// A parsed blog post together with everything needed to decide whether it is still valid.
+#[derive(Clone)]
+pub struct CachedPost {
+    /// Anything besides files that the post was parsed with, e.g. user-defined templates
+    context: u64,
+    dependencies: Dependencies,
+    pub post: Option<BlogPost>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    files: HashMap<PathBuf, FileStamp>,
+    posts: HashMap<PathBuf, CachedPost>,
+    recordings: Vec<Dependencies>,
+}
+
+/// Remembers what previous builds read and produced, so that rebuilds only redo what changed.
+/// Clones share the same cache.
+#[derive(Clone, Default)]
+pub struct BuildCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl BuildCache {
+    fn update_stamp(&self, path: &Path, bytes: &[u8]) -> std::io::Result<u64> {
+        let metadata = std::fs::metadata(path)?;
+        let hash = hash_bytes(bytes);
+        let stamp = FileStamp {
+            modified: metadata.modified()?,
+            len: metadata.len(),
+            hash,
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .files
+            .insert(path.to_path_buf(), stamp);
+        Ok(hash)
+    }
+
+    /// Hashes a file, reusing the previous hash if its size and modification time are unchanged.
+    fn hash_file(&self, path: &Path) -> std::io::Result<u64> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata.modified()?;
+        if let Some(stamp) = self.state.lock().unwrap().files.get(path) {
+            if stamp.modified == modified && stamp.len == metadata.len() {
+                return Ok(stamp.hash);
+            }
+        }
+        self.update_stamp(path, &std::fs::read(path)?)
+    }
+
+    fn record_dependency(&self, path: &Path, hash: Option<u64>) {
+        for recording in self.state.lock().unwrap().recordings.iter_mut() {
+            recording.push((path.to_path_buf(), hash));
+        }
+    }
+
+    /// Reads a file and records it as a dependency of everything that is being recorded.
+    pub fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+        let content = std::fs::read_to_string(path);
+        let hash = match &content {
+            Ok(content) => self.update_stamp(path, content.as_bytes()).ok(),
+            Err(_) => None,
+        };
+        self.record_dependency(path, hash);
+        content
+    }
+
+    /// Hashes a file and records it as a dependency of everything that is being recorded.
+    pub fn track(&self, path: &Path) -> std::io::Result<u64> {
+        let hash = self.hash_file(path);
+        self.record_dependency(path, hash.as_ref().ok().copied());
+        hash
+    }
+
+    /// Runs `build` and returns the files that were read or tracked while it ran.
+    pub fn record<T>(&self, build: impl FnOnce() -> T) -> (T, Dependencies) {
+        self.state.lock().unwrap().recordings.push(Vec::new());
+        let result = build();
+        let dependencies = self
+            .state
+            .lock()
+            .unwrap()
+            .recordings
+            .pop()
+            .unwrap_or_default();
+        (result, dependencies)
+    }
+
+    fn is_up_to_date(&self, dependencies: &Dependencies) -> bool {
+        dependencies
+            .iter()
+            .all(|(path, hash)| self.hash_file(path).ok() == *hash)
+    }
+
+    /// The cached blog post parsed from `path`, if neither the files it depends on nor its context changed.
+    pub fn get_post(&self, path: &Path, context: u64) -> Option<CachedPost> {
+        let cached = self.state.lock().unwrap().posts.get(path).cloned()?;
+        if cached.context != context || !self.is_up_to_date(&cached.dependencies) {
+            return None;
+        }
+        // The files are not read again, but whoever is recording still depends on them.
+        for (path, hash) in &cached.dependencies {
+            self.record_dependency(path, *hash);
+        }
+        Some(cached)
+    }
+
+    pub fn insert_post(
+        &self,
+        path: &Path,
+        context: u64,
+        dependencies: Dependencies,
+        post: Option<BlogPost>,
+        diagnostics: Vec<Diagnostic>,
+    ) {
+        let cached = CachedPost {
+            context,
+            dependencies,
+            post,
+            diagnostics,
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .posts
+            .insert(path.to_path_buf(), cached);
+    }
+}
+
+/// Returns `cache_file`, producing it with `produce` first if it does not exist yet.
+/// The file is written under a temporary name first, so an interrupted build
+/// never leaves a broken file in the cache.
+pub fn cached_file(
+    cache_file: &Path,
+    produce: impl FnOnce(&Path) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if cache_file.exists() {
+        log::trace!("Using cached {}", cache_file.display());
+        return Ok(());
+    }
+    let parent = cache_file.parent().ok_or(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "cannot find parent",
+    ))?;
+    std::fs::create_dir_all(parent)?;
+    let file_name = cache_file
+        .file_name()
+        .map(|x| x.to_string_lossy())
+        .unwrap_or_default();
+    let partial = parent.join(format!("partial-{file_name}"));
+    produce(&partial)?;
+    std::fs::rename(partial, cache_file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_dependencies() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let first = tmp_dir.path().join("first.cms");
+        let missing = tmp_dir.path().join("missing.cms");
+        std::fs::write(&first, "first").unwrap();
+        let cache = BuildCache::default();
+        let ((), dependencies) = cache.record(|| {
+            assert_eq!(cache.read_to_string(&first).unwrap(), "first");
+            let ((), inner) = cache.record(|| assert!(cache.track(&missing).is_err()));
+            assert_eq!(inner, [(missing.clone(), None)]);
+        });
+        assert_eq!(
+            dependencies,
+            [
+                (first.clone(), Some(hash_bytes(b"first"))),
+                (missing.clone(), None)
+            ]
+        );
+        assert!(cache.is_up_to_date(&dependencies));
+        std::fs::write(&first, "changed").unwrap();
+        assert!(!cache.is_up_to_date(&dependencies));
+    }
+
+    #[test]
+    fn test_cached_post() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("post.cms");
+        std::fs::write(&path, "{{Title|Post}}").unwrap();
+        let cache = BuildCache::default();
+        let ((), dependencies) = cache.record(|| {
+            cache.read_to_string(&path).unwrap();
+        });
+        cache.insert_post(&path, 1, dependencies.clone(), None, Vec::new());
+        let (cached, recorded) = cache.record(|| cache.get_post(&path, 1));
+        assert!(cached.is_some());
+        assert_eq!(recorded, dependencies);
+        assert!(cache.get_post(&path, 2).is_none());
+        std::fs::write(&path, "{{Title|Changed post}}").unwrap();
+        assert!(cache.get_post(&path, 1).is_none());
+    }
+
+    #[test]
+    fn test_cached_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let cache_file = tmp_dir.path().join("images/cached.txt");
+        cached_file(&cache_file, |x| Ok(std::fs::write(x, "first")?)).unwrap();
+        cached_file(&cache_file, |_| panic!("should be cached")).unwrap();
+        assert_eq!(std::fs::read_to_string(&cache_file).unwrap(), "first");
+        assert!(cached_file(&tmp_dir.path().join("failed"), |_| Err("failed".into())).is_err());
+        assert!(!tmp_dir.path().join("failed").exists());
+    }
+}
@@ -0,0 +1,86 @@
+/// Escapes text so that it can be placed inside HTML elements and quoted attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes text so that it can be used as a value in the query of a URL. Only letters,
+/// digits and `-._~` are left as they are.
+pub fn encode_query_value(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// The text of an HTML fragment without its tags, e.g. to show an excerpt of it.
+/// Only the entities that [`escape_html`] produces are decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            // Tags separate words, e.g. `first<br/>second`
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if in_tag => (),
+            _ => text.push(c),
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html(r#"if a < b && c > "d" || 'e'"#),
+            "if a &lt; b &amp;&amp; c &gt; &quot;d&quot; || &#39;e&#39;"
+        );
+        assert_eq!(escape_html("#include <vector>"), "#include &lt;vector&gt;");
+        assert_eq!(escape_html("plain text"), "plain text");
+    }
+
+    #[test]
+    fn test_encode_query_value() {
+        assert_eq!(encode_query_value("blog"), "blog");
+        assert_eq!(encode_query_value("my notes"), "my%20notes");
+        assert_eq!(encode_query_value("a&b=c#d+e"), "a%26b%3Dc%23d%2Be");
+        assert_eq!(encode_query_value("über"), "%C3%BCber");
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("<p>a &lt; b &amp;amp; <em>c</em></p>"),
+            " a < b &amp;  c  "
+        );
+        assert_eq!(html_to_text(&escape_html("'x' > \"y\"")), "'x' > \"y\"");
+    }
+}
@@ -1,71 +1,608 @@
-use regex::Regex;
-
 use crate::types::template_type::TemplateType;
 
-struct Replacement {
-    css_class: String,
-    match_keywords: Vec<String>,
+use super::escape::escape_html;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TokenKind {
+    Plain,
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    Variable,
 }
 
-fn wrap_in_span(keyword: &str, css_class: &str) -> String {
-    format!(r#"<span class="code-{css_class}">{keyword}</span>"#)
+impl TokenKind {
+    fn css_class(self) -> Option<&'static str> {
+        match self {
+            TokenKind::Plain => None,
+            TokenKind::Comment => Some("comment"),
+            TokenKind::String => Some("string"),
+            TokenKind::Number => Some("number"),
+            TokenKind::Keyword => Some("keyword"),
+            TokenKind::Type => Some("type"),
+            TokenKind::Variable => Some("variable"),
+        }
+    }
+}
+
+struct Language {
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Ordered so that longer delimiters (e.g. `"""`) are tried first.
+    string_delimiters: &'static [&'static str],
+    /// Treat identifiers starting with an uppercase letter as types.
+    capitalized_types: bool,
+    /// `'` starts a char literal only if it is closed right away, otherwise it is a lifetime.
+    char_literals: bool,
+    /// Line comments only start at the beginning of a word, so `$#` is not a comment.
+    comment_needs_space: bool,
+    preprocessor: bool,
+    variables: bool,
+    table_headers: bool,
+}
+
+const DEFAULT_LANGUAGE: Language = Language {
+    names: &[],
+    keywords: &[],
+    types: &[],
+    line_comment: None,
+    block_comment: None,
+    string_delimiters: &[],
+    capitalized_types: false,
+    char_literals: false,
+    comment_needs_space: false,
+    preprocessor: false,
+    variables: false,
+    table_headers: false,
+};
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for",
+    "goto", "if", "inline", "register", "restrict", "return", "sizeof", "static", "struct",
+    "switch", "typedef", "union", "volatile", "while", "NULL", "true", "false",
+];
+const C_TYPES: &[&str] = &[
+    "bool", "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void",
+    "size_t", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t",
+    "uint64_t",
+];
+const CPP_KEYWORDS: &[&str] = &[
+    "auto",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "constexpr",
+    "continue",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "explicit",
+    "extern",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "nullptr",
+    "operator",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "sizeof",
+    "static",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "throw",
+    "try",
+    "typedef",
+    "typename",
+    "union",
+    "using",
+    "virtual",
+    "volatile",
+    "while",
+    "true",
+    "false",
+];
+
+static LANGUAGES: &[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        types: &[
+            "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "str", "u8",
+            "u16", "u32", "u64", "u128", "usize",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &["\"", "'"],
+        capitalized_types: true,
+        char_literals: true,
+        ..DEFAULT_LANGUAGE
+    },
+    Language {
+        names: &["c", "h"],
+        keywords: C_KEYWORDS,
+        types: C_TYPES,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &["\"", "'"],
+        preprocessor: true,
+        ..DEFAULT_LANGUAGE
+    },
+    Language {
+        names: &["cpp", "c++", "cxx", "hpp"],
+        keywords: CPP_KEYWORDS,
+        types: C_TYPES,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &["\"", "'"],
+        capitalized_types: true,
+        preprocessor: true,
+        ..DEFAULT_LANGUAGE
+    },
+    Language {
+        names: &["python", "py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+        types: &[
+            "bool", "bytes", "dict", "float", "int", "list", "object", "set", "str", "tuple",
+        ],
+        line_comment: Some("#"),
+        string_delimiters: &["\"\"\"", "'''", "\"", "'"],
+        capitalized_types: true,
+        ..DEFAULT_LANGUAGE
+    },
+    Language {
+        names: &["javascript", "js"],
+        keywords: &[
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "debugger",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "export",
+            "extends",
+            "false",
+            "finally",
+            "for",
+            "function",
+            "if",
+            "import",
+            "in",
+            "instanceof",
+            "let",
+            "new",
+            "null",
+            "of",
+            "return",
+            "static",
+            "super",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typeof",
+            "undefined",
+            "var",
+            "void",
+            "while",
+            "yield",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        string_delimiters: &["`", "\"", "'"],
+        capitalized_types: true,
+        ..DEFAULT_LANGUAGE
+    },
+    Language {
+        names: &["toml"],
+        keywords: &["true", "false"],
+        line_comment: Some("#"),
+        string_delimiters: &["\"\"\"", "'''", "\"", "'"],
+        table_headers: true,
+        ..DEFAULT_LANGUAGE
+    },
+    Language {
+        names: &["shell", "sh", "bash", "zsh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "select", "then", "until", "while",
+        ],
+        line_comment: Some("#"),
+        string_delimiters: &["\"", "'"],
+        comment_needs_space: true,
+        variables: true,
+        ..DEFAULT_LANGUAGE
+    },
+];
+
+fn find_language(name: &str) -> Option<&'static Language> {
+    let name = name.trim().to_ascii_lowercase();
+    LANGUAGES.iter().find(|x| x.names.contains(&name.as_str()))
+}
+
+pub fn is_supported_language(name: &str) -> bool {
+    find_language(name).is_some()
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn len_while(text: &str, predicate: impl Fn(char) -> bool) -> usize {
+    text.find(|x| !predicate(x)).unwrap_or(text.len())
 }
 
-fn keyword_replacement(keyword: &str, haystack: &str, rep: &str) -> Option<String> {
-    let re = Regex::new(&format!(r"\b{keyword}\b")).ok()?;
-    let result = re.replace_all(haystack, rep);
-    Some(result.to_string())
+fn line_len(text: &str) -> usize {
+    text.find('\n').unwrap_or(text.len())
+}
+
+fn string_len(rest: &str, delimiter: &str, language: &Language) -> Option<usize> {
+    let body = &rest[delimiter.len()..];
+    if language.char_literals && delimiter == "'" {
+        let mut chars = body.chars();
+        let literal_len = match (chars.next()?, chars.next()?) {
+            ('\\', _) => body.find('\'').filter(|x| *x <= 10)?,
+            (c, '\'') => c.len_utf8(),
+            _ => return None,
+        };
+        return Some(delimiter.len() * 2 + literal_len);
+    }
+    let multi_line = delimiter.len() > 1 || delimiter == "`";
+    let mut index = 0;
+    while index < body.len() {
+        let remaining = &body[index..];
+        if remaining.starts_with(delimiter) {
+            return Some(delimiter.len() * 2 + index);
+        }
+        let c = remaining.chars().next()?;
+        if c == '\n' && !multi_line {
+            return None;
+        }
+        index += c.len_utf8();
+        if c == '\\' {
+            index += remaining[1..]
+                .chars()
+                .next()
+                .map(char::len_utf8)
+                .unwrap_or(0);
+        }
+    }
+    None
+}
+
+fn next_token(
+    rest: &str,
+    language: &Language,
+    line_start: bool,
+    previous: Option<char>,
+) -> (TokenKind, usize) {
+    let c = rest.chars().next().unwrap_or_default();
+    let word_start = previous.is_none_or(char::is_whitespace);
+    if let Some(line_comment) = language.line_comment {
+        if rest.starts_with(line_comment) && (!language.comment_needs_space || word_start) {
+            return (TokenKind::Comment, line_len(rest));
+        }
+    }
+    if let Some((open, close)) = language.block_comment {
+        if let Some(comment) = rest.strip_prefix(open) {
+            let len = comment
+                .find(close)
+                .map(|x| open.len() + x + close.len())
+                .unwrap_or(rest.len());
+            return (TokenKind::Comment, len);
+        }
+    }
+    if language.preprocessor && line_start && c == '#' {
+        return (
+            TokenKind::Keyword,
+            1 + len_while(&rest[1..], is_identifier_char),
+        );
+    }
+    if language.table_headers && line_start && c == '[' {
+        let len = rest.find(']').filter(|x| *x < line_len(rest));
+        return (
+            TokenKind::Type,
+            len.map(|x| x + 1).unwrap_or(line_len(rest)),
+        );
+    }
+    for delimiter in language.string_delimiters {
+        if rest.starts_with(delimiter) {
+            if let Some(len) = string_len(rest, delimiter, language) {
+                return (TokenKind::String, len);
+            }
+        }
+    }
+    if language.variables && c == '$' {
+        let len = match rest[1..].chars().next() {
+            Some('{') => rest.find('}').map(|x| x + 1).unwrap_or(1),
+            Some('@' | '#' | '?' | '$' | '!' | '*') => 2,
+            _ => 1 + len_while(&rest[1..], is_identifier_char),
+        };
+        if len > 1 {
+            return (TokenKind::Variable, len);
+        }
+    }
+    if c.is_ascii_digit() {
+        let mut len = 0;
+        while let Some(next) = rest[len..].chars().next() {
+            let decimal_point = next == '.'
+                && rest[len + 1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|x| x.is_ascii_digit());
+            if !is_identifier_char(next) && !decimal_point {
+                break;
+            }
+            len += next.len_utf8();
+        }
+        return (TokenKind::Number, len);
+    }
+    if c.is_alphabetic() || c == '_' {
+        let len = len_while(rest, is_identifier_char);
+        let word = &rest[..len];
+        let kind = if language.keywords.contains(&word) {
+            TokenKind::Keyword
+        } else if language.types.contains(&word) || (language.capitalized_types && c.is_uppercase())
+        {
+            TokenKind::Type
+        } else {
+            TokenKind::Plain
+        };
+        return (kind, len);
+    }
+    (TokenKind::Plain, c.len_utf8())
 }
 
-fn process_replacements(code: &str, replacements: &[Replacement]) -> String {
-    let mut code = code.to_string();
-    for replacement in replacements {
-        for match_keyword in &replacement.match_keywords {
-            let span = wrap_in_span(match_keyword, &replacement.css_class);
-            code = keyword_replacement(match_keyword, &code, &span).unwrap_or(code);
+fn tokenize<'a>(code: &'a str, language: &Language) -> Vec<(TokenKind, &'a str)> {
+    let mut tokens: Vec<(TokenKind, &str)> = Vec::new();
+    let mut index = 0;
+    let mut plain_start = 0;
+    let mut line_start = true;
+    let mut previous = None;
+    while index < code.len() {
+        let rest = &code[index..];
+        let (kind, len) = next_token(rest, language, line_start, previous);
+        let text = &rest[..len];
+        if kind != TokenKind::Plain {
+            if plain_start < index {
+                tokens.push((TokenKind::Plain, &code[plain_start..index]));
+            }
+            tokens.push((kind, text));
+            plain_start = index + len;
+        }
+        if let Some(last) = text.chars().last() {
+            line_start = last == '\n' || (line_start && text.chars().all(char::is_whitespace));
+            previous = Some(last);
         }
+        index += len;
+    }
+    if plain_start < code.len() {
+        tokens.push((TokenKind::Plain, &code[plain_start..]));
     }
-    code
+    tokens
 }
 
-fn load_replacements() -> Vec<Replacement> {
-    Vec::new()
+fn wrap_in_span(keyword: &str, css_class: &str) -> String {
+    format!(r#"<span class="code-{css_class}">{keyword}</span>"#)
 }
 
-#[macro_export]
-macro_rules! string_vec {
-    ( $( $x:expr ),* ) => {
-        [$($x.to_string(),)*].to_vec()
+fn highlight(code: &str, language: &Language) -> String {
+    tokenize(code, language)
+        .into_iter()
+        .map(|(kind, text)| match kind.css_class() {
+            Some(css_class) => wrap_in_span(&escape_html(text), css_class),
+            None => escape_html(text),
+        })
+        .collect()
+}
+
+pub fn render_code(code: &str, language: Option<&str>) -> String {
+    let code = match language.and_then(find_language) {
+        Some(language) => highlight(code, language),
+        None => escape_html(code),
     };
+    format!("<pre><code>\n{code}\n</code></pre>")
 }
 
 pub fn gen_code(template: &TemplateType) -> String {
-    let replacements = load_replacements();
-    let code = template
+    template
         .get_code()
-        .map(|code| process_replacements(code, &replacements));
-    let code = code.map(|x| format!("<pre><code>\n{x}\n</code></pre>"));
-    code.unwrap_or_default()
+        .map(|(code, language)| render_code(code, language.map(|x| x.as_str())))
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
 mod test {
-    use super::{process_replacements, Replacement};
+    use super::*;
+
+    fn kinds<'a>(code: &'a str, language: &str) -> Vec<(TokenKind, &'a str)> {
+        tokenize(code, find_language(language).unwrap())
+            .into_iter()
+            .filter(|x| x.0 != TokenKind::Plain)
+            .collect()
+    }
 
     #[test]
-    fn test_process_replacements() {
-        let replacements = [Replacement {
-            css_class: "keywords".into(),
-            match_keywords: string_vec!["int", "true"],
-        }];
+    fn test_find_language() {
+        assert!(is_supported_language("Rust"));
+        assert!(is_supported_language("c++"));
+        assert!(is_supported_language("sh"));
+        assert!(!is_supported_language("brainfuck"));
+    }
+
+    #[test]
+    fn test_no_keywords_in_strings() {
         let code = r#"
             int main(){
                 bool x = true;
-                std::string y = "namewithtrue";
+                std::string y = "namewithtrue int";
                 return 0;
             }
         "#;
-        let code = process_replacements(code, &replacements);
-        print!("{}", code);
+        let tokens = kinds(code, "cpp");
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Type, "int"),
+                (TokenKind::Type, "bool"),
+                (TokenKind::Keyword, "true"),
+                (TokenKind::String, "\"namewithtrue int\""),
+                (TokenKind::Keyword, "return"),
+                (TokenKind::Number, "0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rust() {
+        let code = "// sum\nfn sum<'a>(x: &'a str) -> Vec<char> { let c = '\\n'; 1.5 + 0..10 }";
+        let tokens = kinds(code, "rust");
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Comment, "// sum"),
+                (TokenKind::Keyword, "fn"),
+                (TokenKind::Type, "str"),
+                (TokenKind::Type, "Vec"),
+                (TokenKind::Type, "char"),
+                (TokenKind::Keyword, "let"),
+                (TokenKind::String, "'\\n'"),
+                (TokenKind::Number, "1.5"),
+                (TokenKind::Number, "0"),
+                (TokenKind::Number, "10"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_preprocessor() {
+        let tokens = kinds("#include <vector>\n/* block\ncomment */ int", "c");
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Keyword, "#include"),
+                (TokenKind::Comment, "/* block\ncomment */"),
+                (TokenKind::Type, "int"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        let tokens = kinds(
+            "def f():\n    \"\"\"doc\n    string\"\"\"  # comment\n    return None",
+            "python",
+        );
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Keyword, "def"),
+                (TokenKind::String, "\"\"\"doc\n    string\"\"\""),
+                (TokenKind::Comment, "# comment"),
+                (TokenKind::Keyword, "return"),
+                (TokenKind::Keyword, "None"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_javascript() {
+        let tokens = kinds("const s = `a ${b}`; // done", "js");
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Keyword, "const"),
+                (TokenKind::String, "`a ${b}`"),
+                (TokenKind::Comment, "// done"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_toml() {
+        let tokens = kinds("[package]\nname = \"nr_cms\" # name\nlto = true", "toml");
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Type, "[package]"),
+                (TokenKind::String, "\"nr_cms\""),
+                (TokenKind::Comment, "# name"),
+                (TokenKind::Keyword, "true"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shell() {
+        let tokens = kinds(
+            "if [ $# -gt 0 ]; then echo \"$HOME\" ${x}; fi # end",
+            "bash",
+        );
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::Keyword, "if"),
+                (TokenKind::Variable, "$#"),
+                (TokenKind::Number, "0"),
+                (TokenKind::Keyword, "then"),
+                (TokenKind::String, "\"$HOME\""),
+                (TokenKind::Variable, "${x}"),
+                (TokenKind::Keyword, "fi"),
+                (TokenKind::Comment, "# end"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_code() {
+        let html = render_code("let x = 1;", Some("rust"));
+        assert!(html.contains(r#"<span class="code-keyword">let</span>"#));
+        assert!(html.contains(r#"<span class="code-number">1</span>"#));
+        let html = render_code("let x = 1;", None);
+        assert!(!html.contains("span"));
+        let html = render_code("let x = 1;", Some("unknown"));
+        assert!(!html.contains("span"));
+        let html = render_code("#include <vector>\nif (a < b) {}", Some("cpp"));
+        assert!(html.contains("&lt;vector&gt;"));
+        assert!(html.contains("a &lt; b"));
+        assert!(render_code("<b>", None).contains("&lt;b&gt;"));
     }
 }
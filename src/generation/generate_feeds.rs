@@ -0,0 +1,584 @@
+use std::path::{Component, Path};
+
+use chrono::{DateTime, Utc};
+
+use crate::types::{
+    cms_blog::{BlogPost, CMSBlog},
+    cms_site::CMSSite,
+    generation_dirs::GenerationDirs,
+    site_config::OutputMode,
+};
+
+use super::{
+    escape::{encode_query_value, escape_html},
+    generate_website::is_valid_page_name,
+    template_generators::*,
+};
+
+const RSS_FILE: &str = "feed.xml";
+const ATOM_FILE: &str = "atom.xml";
+
+/// A blog together with the name of the page it is shown on.
+struct FeedSource<'a> {
+    page_name: &'a str,
+    blog: &'a CMSBlog,
+}
+
+/// The directory of a blog, normalized so that `feed.xml` can be appended.
+/// Returns `None` if the directory would end up outside the generation directory.
+fn feed_dir(blog: &CMSBlog) -> Option<String> {
+    let components = Path::new(&blog.dir)
+        .components()
+        .map(|x| match x {
+            Component::Normal(x) => x.to_str().map(|x| format!("{x}/")),
+            Component::CurDir => Some(String::new()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(components.concat())
+}
+
+fn feed_sources(cms_site: &CMSSite) -> Vec<FeedSource<'_>> {
+    let mut page_names = cms_site.pages.keys().collect::<Vec<_>>();
+    page_names.sort();
+    page_names
+        .into_iter()
+        .flat_map(|page_name| {
+            cms_site.pages[page_name]
+                .templates
+                .iter()
+                .filter_map(|x| x.get_blog())
+                .map(move |blog| FeedSource { page_name, blog })
+        })
+        .collect()
+}
+
+fn get_base_url(generation_dirs: &impl GenerationDirs) -> Option<&str> {
+    let base_url = generation_dirs.get_config().base_url.as_deref()?;
+    Some(base_url.trim_end_matches('/'))
+}
+
+fn post_url(
+    base_url: &str,
+    source: &FeedSource,
+    post: &BlogPost,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    match generation_dirs.get_config().output_mode {
+        OutputMode::SingleFile => {
+            let id = blog_element_id("post", source.page_name, &post.slug);
+            let (page_name, id) = (
+                encode_query_value(source.page_name),
+                encode_query_value(&id),
+            );
+            format!("{base_url}/?page={page_name}#{id}")
+        }
+        OutputMode::MultiFile => {
+            format!("{base_url}/{}", blog_post_path(source.page_name, post))
+        }
+    }
+}
+
+/// Attributes of the generated HTML that hold URLs, with the space before them and the
+/// opening quote.
+const URL_ATTRIBUTES: &[&str] = &[" src=\"", " href=\"", " srcset=\""];
+
+/// Whether `url` starts with a scheme like `https:` or `data:`.
+fn has_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|x: char| x.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|x| x.is_ascii_alphanumeric() || matches!(x, '+' | '-' | '.'))
+    })
+}
+
+/// `url` relative to the website made absolute. URLs with a scheme, absolute paths and
+/// fragments are kept as they are.
+fn absolute_url(url: &str, base_url: &str) -> String {
+    match url.is_empty() || url.starts_with(['/', '#']) || has_scheme(url) {
+        true => url.to_string(),
+        false => format!("{base_url}/{url}"),
+    }
+}
+
+/// Makes the URLs in the `src`, `srcset` and `href` attributes of generated HTML absolute.
+/// Pages resolve them against the root of the website, but feed readers would resolve them
+/// against the URL of the feed.
+fn absolute_urls(html: &str, base_url: &str) -> String {
+    let base_url = escape_html(base_url);
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+    loop {
+        let next = URL_ATTRIBUTES
+            .iter()
+            .filter_map(|x| Some((rest.find(x)?, *x)))
+            .min_by_key(|x| x.0);
+        let Some((index, attribute)) = next else {
+            break;
+        };
+        let start = index + attribute.len();
+        let Some(len) = rest[start..].find('"') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let value = &rest[start..start + len];
+        if attribute == " srcset=\"" {
+            let candidates = value
+                .split(',')
+                .map(|x| match x.trim().split_once(' ') {
+                    Some((url, size)) => format!("{} {size}", absolute_url(url, &base_url)),
+                    None => absolute_url(x.trim(), &base_url),
+                })
+                .collect::<Vec<_>>();
+            result.push_str(&candidates.join(", "));
+        } else {
+            result.push_str(&absolute_url(value, &base_url));
+        }
+        rest = &rest[start + len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// A blog post as it appears in a feed.
+struct FeedEntry {
+    title: String,
+    url: String,
+    date: DateTime<Utc>,
+    updated: DateTime<Utc>,
+    author: Option<String>,
+    summary: Option<String>,
+    content: String,
+}
+
+fn feed_entries(
+    base_url: &str,
+    source: &FeedSource,
+    mut posts: Vec<&BlogPost>,
+    generation_dirs: &impl GenerationDirs,
+) -> Vec<FeedEntry> {
+    posts.sort_by_key(|x| std::cmp::Reverse(x.post_date));
+    posts
+        .into_iter()
+        .map(|post| FeedEntry {
+            title: escape_html(&post.title),
+            url: escape_html(&post_url(base_url, source, post, generation_dirs)),
+            date: post.post_date,
+            updated: post.updated.unwrap_or(post.post_date),
+            author: post.author.as_deref().map(escape_html),
+            summary: post.summary.as_deref().map(escape_html),
+            content: escape_html(&absolute_urls(
+                &gen_order_preserved_elements(&post.templates, generation_dirs),
+                base_url,
+            )),
+        })
+        .collect()
+}
+
+fn gen_rss(site_title: &str, site_url: &str, feed_url: &str, entries: &[FeedEntry]) -> String {
+    let last_build_date = entries.first().map(|x| x.date).unwrap_or_default();
+    let last_build_date = last_build_date.to_rfc2822();
+    let items = entries
+        .iter()
+        .map(|x| {
+            format!(
+                r#"<item>
+<title>{}</title>
+<link>{}</link>
+<guid isPermaLink="true">{}</guid>
+<pubDate>{}</pubDate>
+<description>{}</description>
+</item>"#,
+                x.title,
+                x.url,
+                x.url,
+                x.date.to_rfc2822(),
+                x.content
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+<channel>
+<title>{site_title}</title>
+<link>{site_url}</link>
+<description>{site_title}</description>
+<atom:link href="{feed_url}" rel="self" type="application/rss+xml"/>
+<lastBuildDate>{last_build_date}</lastBuildDate>
+{items}
+</channel>
+</rss>
+"#
+    )
+}
+
+fn gen_atom(
+    site_title: &str,
+    author: &str,
+    site_url: &str,
+    feed_url: &str,
+    entries: &[FeedEntry],
+) -> String {
+    let updated = entries.iter().map(|x| x.updated).max().unwrap_or_default();
+    let updated = updated.to_rfc3339();
+    let entries = entries
+        .iter()
+        .map(|x| {
+            let author = x
+                .author
+                .as_ref()
+                .map(|x| format!("\n<author><name>{x}</name></author>"))
+                .unwrap_or_default();
+            let summary = x
+                .summary
+                .as_ref()
+                .map(|x| format!("\n<summary>{x}</summary>"))
+                .unwrap_or_default();
+            format!(
+                r#"<entry>
+<title>{}</title>
+<link href="{}"/>
+<id>{}</id>
+<published>{}</published>
+<updated>{}</updated>{author}{summary}
+<content type="html">{}</content>
+</entry>"#,
+                x.title,
+                x.url,
+                x.url,
+                x.date.to_rfc3339(),
+                x.updated.to_rfc3339(),
+                x.content
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>{site_title}</title>
+<link href="{site_url}"/>
+<link rel="self" href="{feed_url}"/>
+<id>{feed_url}</id>
+<updated>{updated}</updated>
+<author><name>{author}</name></author>
+{entries}
+</feed>
+"#
+    )
+}
+
+/// The directory of the feeds of a tag of the blog on `page_name`, which is the directory of
+/// the page of the tag. Only multi-file websites have feeds per tag, since a single file has
+/// no page that such a feed could link to.
+fn tag_feed_dir(
+    page_name: &str,
+    tag_slug: &str,
+    generation_dirs: &impl GenerationDirs,
+) -> Option<String> {
+    get_base_url(generation_dirs)?;
+    if generation_dirs.get_config().output_mode != OutputMode::MultiFile
+        || !is_valid_page_name(page_name)
+    {
+        return None;
+    }
+    Some(tag_path(page_name, tag_slug, OutputMode::MultiFile))
+}
+
+/// Path of the RSS feed of a tag relative to the generation directory, if there is one.
+pub fn tag_feed_path(
+    page_name: &str,
+    tag_slug: &str,
+    generation_dirs: &impl GenerationDirs,
+) -> Option<String> {
+    Some(format!(
+        "{}{RSS_FILE}",
+        tag_feed_dir(page_name, tag_slug, generation_dirs)?
+    ))
+}
+
+/// Generates an RSS and an Atom feed for every blog and, on multi-file websites,
+/// for every tag, returned as pairs of
+/// the path relative to the generation directory and the XML.
+pub fn generate_feeds(
+    cms_site: &CMSSite,
+    generation_dirs: &impl GenerationDirs,
+) -> Vec<(String, String)> {
+    let sources = feed_sources(cms_site);
+    if sources.is_empty() {
+        return Vec::new();
+    }
+    let Some(base_url) = get_base_url(generation_dirs) else {
+        log::info!("Not generating feeds, no base URL is configured (--base-url)");
+        return Vec::new();
+    };
+    let site_title = gen_title(&cms_site.templates);
+    let site_url = escape_html(&format!("{base_url}/"));
+    let author = match &generation_dirs.get_config().author {
+        Some(author) => escape_html(author),
+        None => site_title.clone(),
+    };
+    let mut files = Vec::new();
+    for source in sources {
+        let Some(dir) = feed_dir(source.blog) else {
+            log::warn!(
+                "Not generating feeds for `{}`, the directory is outside the source directory",
+                source.blog.dir
+            );
+            continue;
+        };
+        let posts = source.blog.posts.iter().collect();
+        let entries = feed_entries(base_url, &source, posts, generation_dirs);
+        let rss_url = escape_html(&format!("{base_url}/{dir}{RSS_FILE}"));
+        let atom_url = escape_html(&format!("{base_url}/{dir}{ATOM_FILE}"));
+        files.push((
+            format!("{dir}{RSS_FILE}"),
+            gen_rss(&site_title, &site_url, &rss_url, &entries),
+        ));
+        files.push((
+            format!("{dir}{ATOM_FILE}"),
+            gen_atom(&site_title, &author, &site_url, &atom_url, &entries),
+        ));
+        for tag in source.blog.tags() {
+            let Some(dir) = tag_feed_dir(source.page_name, &tag.slug, generation_dirs) else {
+                continue;
+            };
+            let title = format!("{site_title} #{}", escape_html(tag.name));
+            let entries = feed_entries(base_url, &source, tag.posts, generation_dirs);
+            let rss_url = escape_html(&format!("{base_url}/{dir}{RSS_FILE}"));
+            let atom_url = escape_html(&format!("{base_url}/{dir}{ATOM_FILE}"));
+            files.push((
+                format!("{dir}{RSS_FILE}"),
+                gen_rss(&title, &site_url, &rss_url, &entries),
+            ));
+            files.push((
+                format!("{dir}{ATOM_FILE}"),
+                gen_atom(&title, &author, &site_url, &atom_url, &entries),
+            ));
+        }
+    }
+    files
+}
+
+/// `<link rel="alternate">` tags pointing to the feeds of every blog.
+pub fn gen_feed_links(cms_site: &CMSSite, generation_dirs: &impl GenerationDirs) -> String {
+    let Some(base_url) = get_base_url(generation_dirs) else {
+        return String::new();
+    };
+    let site_title = gen_title(&cms_site.templates);
+    feed_sources(cms_site)
+        .iter()
+        .filter_map(|x| feed_dir(x.blog))
+        .map(|dir| {
+            let rss_url = escape_html(&format!("{base_url}/{dir}{RSS_FILE}"));
+            let atom_url = escape_html(&format!("{base_url}/{dir}{ATOM_FILE}"));
+            format!(
+                r#"<link rel="alternate" type="application/rss+xml" title="{site_title}" href="{rss_url}">
+    <link rel="alternate" type="application/atom+xml" title="{site_title}" href="{atom_url}">"#
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n    ")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        parsing,
+        types::{
+            cms_image::CMSImage,
+            cms_page::CMSPage,
+            generation_dirs::TempGenerationDirs,
+            site_config::SiteConfig,
+            template_type::{TemplateType, TemplateTypeVector},
+        },
+    };
+
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn generation_dirs(output_mode: OutputMode) -> TempGenerationDirs {
+        TempGenerationDirs::with_config(SiteConfig {
+            output_mode,
+            base_url: Some("https://example.com/".to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_absolute_urls() {
+        let html = r##"<a href="blog/a/">a</a> <a href="https://a.b/">b</a> <a href="#c">c</a>
+            <img src="x.jpg" alt="src=&quot;y&quot;" srcset="x.jpg 100w, y.jpg 200w"/>
+            <img src="data:image/png;base64,AA" alt=""/>"##;
+        assert_eq!(
+            absolute_urls(html, "https://example.com"),
+            r##"<a href="https://example.com/blog/a/">a</a> <a href="https://a.b/">b</a> <a href="#c">c</a>
+            <img src="https://example.com/x.jpg" alt="src=&quot;y&quot;" srcset="https://example.com/x.jpg 100w, https://example.com/y.jpg 200w"/>
+            <img src="data:image/png;base64,AA" alt=""/>"##
+        );
+    }
+
+    #[test]
+    fn test_feed_image_urls() {
+        let generation_dirs = generation_dirs(OutputMode::MultiFile);
+        let image = CMSImage {
+            url: "sample.jpg".to_string(),
+            copy_asset: true,
+            size: Some(200),
+            alt: "Me".to_string(),
+            caption: None,
+            link: None,
+            align: None,
+        };
+        let post = BlogPost {
+            slug: "photo".to_string(),
+            title: "Photo".to_string(),
+            post_date: Default::default(),
+            updated: None,
+            tags: Vec::new(),
+            author: None,
+            summary: None,
+            cover: None,
+            draft: false,
+            templates: Vec::from([TemplateType::Image(image)]),
+        };
+        let blog = CMSBlog {
+            dir: "blog_files/".to_string(),
+            posts: Vec::from([post]),
+            next_publication: None,
+        };
+        let cms_site = CMSSite {
+            templates: Vec::new(),
+            pages: HashMap::from([(
+                "blog".to_string(),
+                CMSPage {
+                    templates: Vec::from([TemplateType::Blog(blog)]),
+                },
+            )]),
+            diagnostics: Vec::new(),
+        };
+        let feeds = generate_feeds(&cms_site, &generation_dirs);
+        assert_eq!(feeds.len(), 2);
+        for (_, feed) in feeds {
+            assert!(feed.contains("&lt;img src=&quot;https://example.com/sample.jpg&quot;"));
+            assert!(!feed.contains("src=&quot;sample.jpg"));
+        }
+    }
+
+    #[test]
+    fn test_feed_dir() {
+        let blog = |dir: &str| CMSBlog {
+            dir: dir.to_string(),
+            posts: Vec::new(),
+            next_publication: None,
+        };
+        assert_eq!(feed_dir(&blog("blog_files/")).unwrap(), "blog_files/");
+        assert_eq!(feed_dir(&blog("./a/b")).unwrap(), "a/b/");
+        assert!(feed_dir(&blog("../outside")).is_none());
+        assert!(feed_dir(&blog("/absolute")).is_none());
+    }
+
+    #[test]
+    fn test_generate_feeds() {
+        let generation_dirs = generation_dirs(OutputMode::SingleFile);
+        let cms_site = parsing::parse_file(&generation_dirs).unwrap();
+        let feeds = generate_feeds(&cms_site, &generation_dirs);
+        assert_eq!(feeds.len(), 2);
+
+        let (path, rss) = &feeds[0];
+        assert_eq!(path, "blog_files/feed.xml");
+        assert!(rss.contains("<title>Learning code</title>"));
+        assert!(rss.contains("<link>https://example.com/?page=blog#post-blog-learning-code</link>"));
+        assert!(rss.contains(" +0000</pubDate>"));
+        assert!(rss.contains("&lt;p&gt;"));
+        assert!(
+            rss.find("Learning code").unwrap() < rss.find("My first toy").unwrap(),
+            "newest post comes first"
+        );
+
+        let (path, atom) = &feeds[1];
+        assert_eq!(path, "blog_files/atom.xml");
+        assert!(atom.contains("<id>https://example.com/blog_files/atom.xml</id>"));
+        assert!(atom.contains("+00:00</updated>"));
+        assert!(atom.contains(r#"<content type="html">"#));
+    }
+
+    #[test]
+    fn test_feed_post_urls() {
+        let single_file = generation_dirs(OutputMode::SingleFile);
+        let generation_dirs = generation_dirs(OutputMode::MultiFile);
+        let cms_site = parsing::parse_file(&generation_dirs).unwrap();
+        let feeds = generate_feeds(&cms_site, &generation_dirs);
+        assert!(feeds[0]
+            .1
+            .contains("<link>https://example.com/blog/learning-code/</link>"));
+
+        let blog = cms_site.pages["blog"].templates.get_blog().unwrap();
+        let source = FeedSource {
+            page_name: "my notes & more",
+            blog,
+        };
+        let post = &blog.posts[0];
+        assert_eq!(
+            post_url("https://example.com", &source, post, &single_file),
+            format!(
+                "https://example.com/?page=my%20notes%20%26%20more#post-my%20notes%20%26%20more-{}",
+                post.slug
+            )
+        );
+    }
+
+    #[test]
+    fn test_tag_feeds() {
+        let single_file = generation_dirs(OutputMode::SingleFile);
+        let generation_dirs = generation_dirs(OutputMode::MultiFile);
+        let cms_site = parsing::parse_file(&generation_dirs).unwrap();
+        let feeds = generate_feeds(&cms_site, &generation_dirs);
+        let paths = feeds.iter().map(|x| x.0.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "blog_files/feed.xml",
+                "blog_files/atom.xml",
+                "blog/tags/childhood/feed.xml",
+                "blog/tags/childhood/atom.xml",
+                "blog/tags/code/feed.xml",
+                "blog/tags/code/atom.xml",
+            ]
+        );
+        let code = &feeds[4].1;
+        assert!(code.contains("<title>Nareshkumar Rao #code</title>"));
+        assert!(code.contains("Learning code"));
+        assert!(!code.contains("My first toy"));
+
+        assert!(code.contains(r#"<atom:link href="https://example.com/blog/tags/code/feed.xml""#));
+
+        assert!(tag_feed_path("blog", "code", &single_file).is_none());
+        assert!(tag_feed_path("../blog", "code", &generation_dirs).is_none());
+        assert_eq!(
+            tag_feed_path("blog", "code", &generation_dirs).unwrap(),
+            "blog/tags/code/feed.xml"
+        );
+    }
+
+    #[test]
+    fn test_no_base_url() {
+        let generation_dirs = TempGenerationDirs::default();
+        let cms_site = parsing::parse_file(&generation_dirs).unwrap();
+        assert!(generate_feeds(&cms_site, &generation_dirs).is_empty());
+        assert_eq!(gen_feed_links(&cms_site, &generation_dirs), String::new());
+    }
+
+    #[test]
+    fn test_gen_feed_links() {
+        let generation_dirs = generation_dirs(OutputMode::SingleFile);
+        let cms_site = parsing::parse_file(&generation_dirs).unwrap();
+        let links = gen_feed_links(&cms_site, &generation_dirs);
+        assert!(links.contains(r#"href="https://example.com/blog_files/feed.xml""#));
+        assert!(links.contains(r#"type="application/atom+xml""#));
+    }
+}
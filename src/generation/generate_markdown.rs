@@ -0,0 +1,142 @@
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+use crate::types::{generation_dirs::GenerationDirs, template_type::TemplateType};
+
+use super::generate_code::render_code;
+
+/// Renders Markdown to HTML. Inline HTML in the source is escaped like any other text,
+/// raw markup has to go through the `Html` template instead. `image_url` may replace the URL
+/// of an image.
+pub fn render_markdown(markdown: &str, image_url: impl Fn(&str) -> Option<String>) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    let mut events = Vec::new();
+    let mut code_block: Option<(Option<String>, String)> = None;
+    for event in Parser::new_ext(markdown, options) {
+        match (event, code_block.as_mut()) {
+            (Event::Start(Tag::CodeBlock(kind)), _) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().map(String::from),
+                    CodeBlockKind::Indented => None,
+                };
+                code_block = Some((language, String::new()));
+            }
+            (Event::End(TagEnd::CodeBlock), Some((language, code))) => {
+                let code = code.strip_suffix('\n').unwrap_or(code);
+                events.push(Event::Html(render_code(code, language.as_deref()).into()));
+                code_block = None;
+            }
+            (Event::Text(text), Some((_, code))) => code.push_str(&text),
+            (Event::Html(html) | Event::InlineHtml(html), _) => events.push(Event::Text(html)),
+            (
+                Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }),
+                _,
+            ) => {
+                let dest_url = image_url(&dest_url).map_or(dest_url, Into::into);
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            (event, _) => events.push(event),
+        }
+    }
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+    html
+}
+
+/// Renders a `Markdown` template, copying its local images like those of `{{Image}}`.
+pub fn gen_markdown(template: &TemplateType, generation_dirs: &impl GenerationDirs) -> String {
+    let TemplateType::Markdown { content, images } = template else {
+        return String::new();
+    };
+    let html = render_markdown(content, |url| {
+        let image = images.get(url)?;
+        if !image.copy_asset {
+            return Some(image.url.clone());
+        }
+        match generation_dirs.copy_asset_img_srcset(&image.url, image.size) {
+            Ok(responsive) => Some(responsive.image.path),
+            Err(e) => {
+                log::error!("Could not copy image `{}`: {e}", image.url);
+                None
+            }
+        }
+    });
+    format!("<div class=\"markdown\">\n{html}</div>")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::{cms_image::CMSImage, generation_dirs::TempGenerationDirs};
+
+    use super::*;
+
+    #[test]
+    fn test_render_markdown() {
+        let html = render_markdown("# Heading\n\nSome *emphasis*, `code` and a [link](https://a.b).\n\n- first\n- second\n", |_| None);
+        assert!(html.contains("<h1>Heading</h1>"));
+        assert!(html.contains("<em>emphasis</em>"));
+        assert!(html.contains("<code>code</code>"));
+        assert!(html.contains(r#"<a href="https://a.b">link</a>"#));
+        assert!(html.contains("<li>second</li>"));
+    }
+
+    #[test]
+    fn test_fenced_code() {
+        let html = render_markdown("```\nint main(){}\n```\n", |_| None);
+        assert!(html.contains(&render_code("int main(){}", None)));
+        let html = render_markdown("```cpp\nint main(){}\n```\n", |_| None);
+        assert!(html.contains(r#"<span class="code-type">int</span>"#));
+    }
+
+    #[test]
+    fn test_inline_html_is_escaped() {
+        let html = render_markdown(
+            "<script>alert(1)</script>\n\nA <b>bold</b> claim & more\n",
+            |_| None,
+        );
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("A &lt;b&gt;bold&lt;/b&gt; claim &amp; more"));
+    }
+
+    #[test]
+    fn test_gen_markdown() {
+        let generation_dirs = TempGenerationDirs::default();
+        let test = TemplateType::Markdown {
+            content: "**first**".to_string(),
+            images: Default::default(),
+        };
+        assert!(gen_markdown(&test, &generation_dirs).contains("<strong>first</strong>"));
+        let image = CMSImage {
+            url: "data:image/png;base64,AAAA".to_string(),
+            copy_asset: false,
+            size: None,
+            alt: String::new(),
+            caption: None,
+            link: None,
+            align: None,
+        };
+        let test = TemplateType::Markdown {
+            content: "![a](a.png) ![b](b.png)".to_string(),
+            images: [("a.png".to_string(), image)].into(),
+        };
+        let html = gen_markdown(&test, &generation_dirs);
+        assert!(html.contains(r#"<img src="data:image/png;base64,AAAA" alt="a" />"#));
+        assert!(html.contains(r#"<img src="b.png" alt="b" />"#));
+        let test = TemplateType::Name {
+            name: "abc".to_string(),
+        };
+        assert_eq!(gen_markdown(&test, &generation_dirs), String::new());
+    }
+}
@@ -1,27 +1,124 @@
+use std::path::{Component, Path};
+
+use chrono::{DateTime, Utc};
+
 use crate::{
     assets, parsing,
-    types::{cms_site::CMSSite, generation_dirs::GenerationDirs},
+    types::{
+        cms_site::CMSSite,
+        diagnostic::{Diagnostic, Severity},
+        generation_dirs::GenerationDirs,
+        site_config::OutputMode,
+    },
+};
+
+use super::{
+    escape::escape_html,
+    generate_feeds::{gen_feed_links, generate_feeds, tag_feed_path},
+    page_generator::*,
+    template_generators::*,
 };
 
-use super::{page_generator::*, template_generators::*};
+/// The outcome of generating the website.
+pub struct Build {
+    pub result: Result<(), String>,
+    /// When the next scheduled blog post is due, the website has to be generated again then
+    pub next_publication: Option<DateTime<Utc>>,
+}
 
-pub fn generate_website(generation_dirs: &impl GenerationDirs) {
-    let index_file = parsing::parse_file(generation_dirs);
-    match index_file {
-        Ok(index_file) => {
-            let html = generate_html(&index_file, generation_dirs);
-            write_file(generation_dirs.in_gen("index.html"), &html).unwrap_or_else(|e| {
-                log::error!("Could not write HTML to file: {}", e.to_string());
-            });
-        }
+pub fn generate_website(generation_dirs: &impl GenerationDirs) -> Result<(), String> {
+    build(generation_dirs).result
+}
+
+/// Like [`generate_website`], but first reloads the configuration, which may have changed
+/// since the last build, and also tells when the website has to be generated again.
+pub fn build_website(generation_dirs: &mut impl GenerationDirs) -> Build {
+    if let Err(e) = generation_dirs.reload_config() {
+        log::error!("{e}");
+        return Build {
+            result: Err(e),
+            next_publication: None,
+        };
+    }
+    build(generation_dirs)
+}
+
+fn build(generation_dirs: &impl GenerationDirs) -> Build {
+    let index_file = match parsing::parse_file(generation_dirs) {
+        Ok(index_file) => index_file,
         Err(e) => {
-            log::error!("Could not load CMS site: {}", e.to_string());
+            let e = format!("Could not load CMS site: {e}");
+            log::error!("{e}");
+            return Build {
+                result: Err(e),
+                next_publication: None,
+            };
         }
+    };
+    Build {
+        result: write_website(&index_file, generation_dirs),
+        next_publication: next_publication(&index_file),
     }
 }
 
-fn write_file(file_path: std::path::PathBuf, html: &str) -> Result<(), std::io::Error> {
+/// The earliest date at which a scheduled blog post of the site is due.
+fn next_publication(cms_site: &CMSSite) -> Option<DateTime<Utc>> {
+    cms_site
+        .pages
+        .values()
+        .flat_map(|x| x.templates.iter().filter_map(|x| x.get_blog()))
+        .filter_map(|x| x.next_publication)
+        .min()
+}
+
+fn write_website(
+    index_file: &CMSSite,
+    generation_dirs: &impl GenerationDirs,
+) -> Result<(), String> {
     log::info!("Generating website");
+    let mut files = match generation_dirs.get_config().output_mode {
+        OutputMode::SingleFile => Vec::from([(
+            "index.html".to_string(),
+            generate_html(index_file, generation_dirs),
+        )]),
+        OutputMode::MultiFile => generate_multi_file_html(index_file, generation_dirs),
+    };
+    files.extend(generate_feeds(index_file, generation_dirs));
+    for (path, html) in files {
+        write_file(generation_dirs.in_gen(&path), &html).map_err(|e| {
+            let e = format!("Could not write {path}: {e}");
+            log::error!("{e}");
+            e
+        })?;
+    }
+    report_diagnostics(&index_file.diagnostics)
+}
+
+/// Logs every diagnostic and fails the build if any of them is an error.
+fn report_diagnostics(diagnostics: &[Diagnostic]) -> Result<(), String> {
+    for diagnostic in diagnostics {
+        match diagnostic.severity {
+            Severity::Error => log::error!("{diagnostic}"),
+            Severity::Warning => log::warn!("{diagnostic}"),
+        }
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|x| x.severity == Severity::Error)
+        .map(|x| x.render())
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(errors.join("\n\n"))
+}
+
+/// Writes the file, unless it already has the same content.
+fn write_file(file_path: std::path::PathBuf, html: &str) -> Result<(), std::io::Error> {
+    if std::fs::read(&file_path).is_ok_and(|x| x == html.as_bytes()) {
+        log::trace!("{} is unchanged", file_path.display());
+        return Ok(());
+    }
     let parent = file_path.parent().ok_or(std::io::Error::new(
         std::io::ErrorKind::NotFound,
         "Could not get parent",
@@ -31,33 +128,134 @@ fn write_file(file_path: std::path::PathBuf, html: &str) -> Result<(), std::io::
     Ok(())
 }
 
-fn generate_html(cms_site: &CMSSite, generation_dirs: &impl GenerationDirs) -> String {
+/// The layout shared by every generated HTML file. `depth` is the number of directories
+/// between the file and the generation directory, so that relative URLs keep working.
+fn gen_layout(
+    cms_site: &CMSSite,
+    title: &str,
+    content: &str,
+    depth: usize,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
     let templates = &cms_site.templates;
-    let pages = &cms_site.pages;
-    let title = gen_title(templates);
-    let navbar = gen_navbar(templates);
+    let navbar = gen_navbar(templates, generation_dirs);
     let nr_cms_info = gen_nr_cms_info(templates);
+    let site_title = gen_title(templates);
+    let feed_links = gen_feed_links(cms_site, generation_dirs);
     let style = assets::styles::SITE_STYLE;
-    let script = assets::scripts::PAGE_LOGIC;
-    let pages = gen_pages(pages, generation_dirs);
-    let site = format!(
+    let date_script = match generation_dirs.get_config().dates.localize {
+        true => assets::scripts::DATE_LOGIC,
+        false => "",
+    };
+    let script = match generation_dirs.get_config().output_mode {
+        OutputMode::SingleFile => format!("{}{date_script}", assets::scripts::PAGE_LOGIC),
+        OutputMode::MultiFile => date_script.to_string(),
+    };
+    let base = match depth {
+        0 => String::new(),
+        depth => format!(r#"<base href="{}">"#, "../".repeat(depth)),
+    };
+    let config = generation_dirs.get_config();
+    let lang = config
+        .language
+        .as_ref()
+        .map(|x| format!(r#" lang="{}""#, escape_html(x)))
+        .unwrap_or_default();
+    let author = config
+        .author
+        .as_ref()
+        .map(|x| format!(r#"<meta name="author" content="{}">"#, escape_html(x)))
+        .unwrap_or_default();
+    let default_page = escape_html(&config.default_page);
+    format!(
         r#"
-    <html>
+    <html{lang}>
     <head>
+    <meta charset="utf-8">
+    {author}
+    {base}
+    {feed_links}
     {script}
     {style}
     <title>{title}</title>
     </head>
-    <body>
-    <h1>{title}</h1>
+    <body data-default-page="{default_page}">
+    <h1>{site_title}</h1>
     {navbar}
-    {pages}
+    {content}
     {nr_cms_info}
     </body>
     </html>
     "#
-    );
-    site
+    )
+}
+
+fn generate_html(cms_site: &CMSSite, generation_dirs: &impl GenerationDirs) -> String {
+    let title = gen_title(&cms_site.templates);
+    let pages = gen_pages(&cms_site.pages, generation_dirs);
+    gen_layout(cms_site, &title, &pages, 0, generation_dirs)
+}
+
+/// Page names end up in paths, so they must not be able to leave the generation directory.
+pub fn is_valid_page_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+/// Generates one HTML file per page and blog post, returned as pairs of
+/// the path relative to the generation directory and the HTML.
+fn generate_multi_file_html(
+    cms_site: &CMSSite,
+    generation_dirs: &impl GenerationDirs,
+) -> Vec<(String, String)> {
+    let site_title = gen_title(&cms_site.templates);
+    let mut files = Vec::new();
+    for (name, page) in &cms_site.pages {
+        if !is_valid_page_name(name) {
+            log::warn!("Skipping page `{name}`, its name cannot be used as a path");
+            continue;
+        }
+        let content = gen_page(name, page, generation_dirs);
+        if *name == generation_dirs.get_config().default_page {
+            let html = gen_layout(cms_site, &site_title, &content, 0, generation_dirs);
+            files.push(("index.html".to_string(), html));
+        }
+        let html = gen_layout(cms_site, &site_title, &content, 1, generation_dirs);
+        files.push((format!("{name}/index.html"), html));
+
+        for blog in page.templates.iter().filter_map(|x| x.get_blog()) {
+            for post in &blog.posts {
+                let content = gen_blog_post(post, name, generation_dirs);
+                let title = format!("{} | {site_title}", escape_html(&post.title));
+                let content = format!(r#"<div class="page current-page">{content}</div>"#);
+                let html = gen_layout(cms_site, &title, &content, 2, generation_dirs);
+                files.push((format!("{}index.html", blog_post_path(name, post)), html));
+            }
+            for number in 2..=blog_page_count(blog, generation_dirs) {
+                let content = gen_blog_page(blog, name, number, generation_dirs);
+                let content = format!(
+                    r#"<div class="page current-page"><div class="blog">{content}</div></div>"#
+                );
+                let title = format!("Page {number} | {site_title}");
+                let html = gen_layout(cms_site, &title, &content, 3, generation_dirs);
+                let path = blog_page_path(name, number, OutputMode::MultiFile);
+                files.push((format!("{path}index.html"), html));
+            }
+            for tag in blog.tags() {
+                let feed_path = tag_feed_path(name, &tag.slug, generation_dirs);
+                let content = gen_tag_page(&tag, name, feed_path.as_deref(), generation_dirs);
+                let content = format!(r#"<div class="page current-page">{content}</div>"#);
+                let title = format!("#{} | {site_title}", escape_html(tag.name));
+                let html = gen_layout(cms_site, &title, &content, 3, generation_dirs);
+                let path = tag_path(name, &tag.slug, OutputMode::MultiFile);
+                files.push((format!("{path}index.html"), html));
+            }
+        }
+    }
+    files
 }
 
 #[cfg(test)]
@@ -65,7 +263,12 @@ mod test {
     use std::collections::HashMap;
 
     use crate::types::{
-        cms_page::CMSPage, cms_site::CMSSite, generation_dirs::TempGenerationDirs,
+        cms_blog::{BlogPost, CMSBlog},
+        cms_page::CMSPage,
+        cms_site::CMSSite,
+        generation_dirs::{StandardGenerationDirs, TempGenerationDirs},
+        program_args::ProgramArgs,
+        site_config::{BlogConfig, SiteConfig, CONFIG_FILE},
         template_type::TemplateType,
     };
 
@@ -74,21 +277,231 @@ mod test {
     #[test]
     fn test_generate_html() {
         let cms_site = CMSSite {
-            original_content: Default::default(),
             templates: Vec::from([TemplateType::Title {
                 title: "TestSite".to_string(),
             }]),
             pages: HashMap::<String, CMSPage>::new(),
+            diagnostics: Vec::new(),
         };
         let html = generate_html(&cms_site, &TempGenerationDirs::default());
         assert!(html.contains("TestSite"));
     }
 
+    #[test]
+    fn test_generate_website() {
+        let generation_dirs = TempGenerationDirs::default();
+        generate_website(&generation_dirs).unwrap();
+        assert!(generation_dirs.in_gen("index.html").exists());
+        assert!(!generation_dirs.in_gen("home/index.html").exists());
+    }
+
+    #[test]
+    fn test_build_website_reloads_config() {
+        let source_dir = tempfile::tempdir().unwrap();
+        std::fs::write(source_dir.path().join("index.cms"), "{{Title|Site}}").unwrap();
+        let generation_dir = tempfile::tempdir().unwrap();
+        let program_args = ProgramArgs {
+            source_dir: source_dir.path().to_string_lossy().to_string(),
+            generation_dir: generation_dir.path().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let mut generation_dirs = StandardGenerationDirs::try_from(program_args).unwrap();
+        build_website(&mut generation_dirs).result.unwrap();
+        let config_file = source_dir.path().join(CONFIG_FILE);
+        std::fs::write(&config_file, r#"language = "de""#).unwrap();
+        build_website(&mut generation_dirs).result.unwrap();
+        let index = std::fs::read_to_string(generation_dirs.in_gen("index.html")).unwrap();
+        assert!(index.contains(r#"<html lang="de">"#));
+
+        std::fs::write(&config_file, "language = 1").unwrap();
+        let build = build_website(&mut generation_dirs);
+        assert!(build.result.unwrap_err().contains(CONFIG_FILE));
+    }
+
+    #[test]
+    fn test_generate_website_multi_file() {
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            output_mode: OutputMode::MultiFile,
+            ..Default::default()
+        });
+        generate_website(&generation_dirs).unwrap();
+        let index = std::fs::read_to_string(generation_dirs.in_gen("index.html")).unwrap();
+        assert!(index.contains(r#"id="page-home""#));
+        assert!(!index.contains("<base"));
+        assert!(!index.contains(r#"id="page-blog""#));
+        let blog = std::fs::read_to_string(generation_dirs.in_gen("blog/index.html")).unwrap();
+        assert!(blog.contains(r#"<base href="../">"#));
+        assert!(blog.contains(r#"<a href="blog/learning-code/">"#));
+        assert!(generation_dirs.in_gen("contact/index.html").exists());
+        let post = generation_dirs.in_gen("blog/learning-code/index.html");
+        let post = std::fs::read_to_string(post).unwrap();
+        assert!(post.contains(r#"<base href="../../">"#));
+        assert!(post.contains("<title>Learning code | Nareshkumar Rao</title>"));
+        assert!(post.contains(r#"<a href="home/">home</a>"#));
+        assert!(post.contains(r#"<a href="blog/tags/code/" class="tag">#code</a>"#));
+        let tag = generation_dirs.in_gen("blog/tags/childhood/index.html");
+        let tag = std::fs::read_to_string(tag).unwrap();
+        assert!(tag.contains(r#"<base href="../../../">"#));
+        assert!(tag.contains("<title>#childhood | Nareshkumar Rao</title>"));
+        assert!(tag.contains(r#"id="post-blog-my-first-toy""#));
+        assert!(!generation_dirs.in_gen("blog/page/2/index.html").exists());
+    }
+
+    /// A site with a blog on the pages `blog` and `news`, each with a post `hello` tagged `news`.
+    fn site_with_two_blogs() -> CMSSite {
+        let post = |title: &str| BlogPost {
+            slug: "hello".to_string(),
+            title: title.to_string(),
+            post_date: Default::default(),
+            updated: None,
+            tags: Vec::from(["news".to_string()]),
+            author: None,
+            summary: None,
+            cover: None,
+            draft: false,
+            templates: Vec::new(),
+        };
+        let page = |title: &str| CMSPage {
+            templates: Vec::from([TemplateType::Blog(CMSBlog {
+                dir: format!("{title}/"),
+                posts: Vec::from([post(title)]),
+                next_publication: None,
+            })]),
+        };
+        CMSSite {
+            templates: Vec::new(),
+            pages: HashMap::from([
+                ("blog".to_string(), page("First")),
+                ("news".to_string(), page("Second")),
+            ]),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_single_file_blogs_on_different_pages() {
+        let html = generate_html(&site_with_two_blogs(), &TempGenerationDirs::default());
+        let ids = [
+            "tag-blog-news",
+            "tag-news-news",
+            "post-blog-hello",
+            "post-news-hello",
+            "blog-page-blog-1",
+            "blog-page-news-1",
+        ];
+        for id in ids {
+            assert_eq!(html.matches(&format!(r#"id="{id}""#)).count(), 1, "{id}");
+        }
+        for id in [
+            "tag-blog-news",
+            "tag-news-news",
+            "post-blog-hello",
+            "post-news-hello",
+        ] {
+            assert!(html.contains(&format!(r##"href="#{id}""##)), "{id}");
+        }
+    }
+
+    #[test]
+    fn test_multi_file_blogs_on_different_pages() {
+        let cms_site = site_with_two_blogs();
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            output_mode: OutputMode::MultiFile,
+            ..Default::default()
+        });
+        let files = generate_multi_file_html(&cms_site, &generation_dirs);
+        let file = |path: &str| &files.iter().find(|x| x.0 == path).unwrap().1;
+        assert!(file("blog/hello/index.html").contains("First"));
+        assert!(file("news/hello/index.html").contains("Second"));
+        assert!(file("news/index.html").contains(r#"<a href="news/hello/">Second</a>"#));
+        assert!(file("news/tags/news/index.html").contains("Second"));
+        assert!(!file("blog/tags/news/index.html").contains("Second"));
+    }
+
+    #[test]
+    fn test_generate_website_pagination() {
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            output_mode: OutputMode::MultiFile,
+            blog: BlogConfig {
+                posts_per_page: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        generate_website(&generation_dirs).unwrap();
+        let blog = std::fs::read_to_string(generation_dirs.in_gen("blog/index.html")).unwrap();
+        assert!(blog.contains("Learning code") && !blog.contains("My first toy"));
+        let page = generation_dirs.in_gen("blog/page/2/index.html");
+        let page = std::fs::read_to_string(page).unwrap();
+        assert!(page.contains(r#"<base href="../../../">"#));
+        assert!(page.contains("<title>Page 2 | Nareshkumar Rao</title>"));
+        assert!(page.contains("My first toy"));
+        assert!(page.contains(r#"<a href="blog/">1</a>"#));
+    }
+
+    #[test]
+    fn test_generate_html_config() {
+        let cms_site = CMSSite {
+            templates: Vec::new(),
+            pages: HashMap::<String, CMSPage>::new(),
+            diagnostics: Vec::new(),
+        };
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            language: Some("de".to_string()),
+            author: Some("Naresh".to_string()),
+            default_page: "start".to_string(),
+            ..Default::default()
+        });
+        let html = generate_html(&cms_site, &generation_dirs);
+        assert!(html.contains(r#"<html lang="de">"#));
+        assert!(html.contains(r#"<meta name="author" content="Naresh">"#));
+        assert!(html.contains(r#"data-default-page="start""#));
+    }
+
+    #[test]
+    fn test_generate_website_feeds() {
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            base_url: Some("https://example.com".to_string()),
+            ..Default::default()
+        });
+        generate_website(&generation_dirs).unwrap();
+        assert!(generation_dirs.in_gen("blog_files/feed.xml").exists());
+        assert!(generation_dirs.in_gen("blog_files/atom.xml").exists());
+        let index = std::fs::read_to_string(generation_dirs.in_gen("index.html")).unwrap();
+        assert!(index.contains(r#"<link rel="alternate" type="application/rss+xml""#));
+    }
+
+    #[test]
+    fn test_is_valid_page_name() {
+        assert!(is_valid_page_name("home"));
+        assert!(!is_valid_page_name("../home"));
+        assert!(!is_valid_page_name("a/b"));
+        assert!(!is_valid_page_name(""));
+    }
+
+    #[test]
+    fn test_report_diagnostics() {
+        let warning = Diagnostic::new(Severity::Warning, "warning", Path::new("a"), "", 0..0);
+        let error = Diagnostic::new(Severity::Error, "broken", Path::new("a"), "", 0..0);
+        assert!(report_diagnostics(std::slice::from_ref(&warning)).is_ok());
+        let result = report_diagnostics(&[warning, error]).unwrap_err();
+        assert!(result.starts_with("error: broken"));
+    }
+
     #[test]
     fn test_write_to_file() {
         let tmpfile = tempfile::tempdir().unwrap();
         let path = tmpfile.path().join("test.file");
         write_file(path.to_path_buf(), "content").unwrap();
         assert!(path.exists());
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        write_file(path.to_path_buf(), "content").unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().modified().unwrap(),
+            modified
+        );
+        write_file(path.to_path_buf(), "changed").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "changed");
     }
 }
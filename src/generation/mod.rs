@@ -1,4 +1,9 @@
+mod escape;
 mod generate_code;
+mod generate_feeds;
+mod generate_markdown;
 pub mod generate_website;
 mod page_generator;
 mod template_generators;
+
+pub use generate_code::is_supported_language;
@@ -1,8 +1,29 @@
 use std::collections::HashMap;
 
-use crate::types::{cms_page::CMSPage, generation_dirs::GenerationDirs};
+use crate::types::{cms_page::CMSPage, generation_dirs::GenerationDirs, site_config::OutputMode};
 
-use super::template_generators::*;
+use super::{escape::escape_html, template_generators::*};
+
+pub fn gen_page(name: &str, page: &CMSPage, generation_dirs: &impl GenerationDirs) -> String {
+    let blog = gen_blog(&page.templates, name, generation_dirs);
+    let name = escape_html(name);
+    let class = match generation_dirs.get_config().output_mode {
+        OutputMode::SingleFile => "page",
+        OutputMode::MultiFile => "page current-page",
+    };
+    let templates = &page.templates;
+    let order_preserved_elements = gen_order_preserved_elements(templates, generation_dirs);
+    let links = gen_links(templates, generation_dirs);
+    format!(
+        r#"
+        <div id="page-{name}" class="{class}">
+        {order_preserved_elements}
+        {links}
+        {blog}
+        </div>
+        "#
+    )
+}
 
 pub fn gen_pages(
     pages: &HashMap<String, CMSPage>,
@@ -10,21 +31,7 @@ pub fn gen_pages(
 ) -> String {
     let mut pages_string = String::new();
     for (name, page) in pages {
-        let templates = &page.templates;
-        let order_preserved_elements = gen_order_preserved_elements(templates, generation_dirs);
-        let links = gen_links(templates);
-        let blog = gen_blog(templates, generation_dirs);
-
-        let page_string = format!(
-            r#"
-        <div id="page-{name}" class="page">
-        {order_preserved_elements}
-        {links}
-        {blog}
-        </div>
-        "#
-        );
-        pages_string.push_str(&page_string);
+        pages_string.push_str(&gen_page(name, page, generation_dirs));
     }
     pages_string
 }
@@ -34,10 +41,14 @@ mod test {
     use std::collections::HashMap;
 
     use crate::types::{
-        cms_page::CMSPage, generation_dirs::TempGenerationDirs, template_type::TemplateType,
+        cms_image::CMSImage,
+        cms_page::CMSPage,
+        generation_dirs::TempGenerationDirs,
+        site_config::{OutputMode, SiteConfig},
+        template_type::TemplateType,
     };
 
-    use super::gen_pages;
+    use super::{gen_page, gen_pages};
 
     #[test]
     fn test_gen_pages() {
@@ -53,12 +64,17 @@ mod test {
                     },
                     TemplateType::Code {
                         code: "Third".to_string(),
+                        language: None,
                     },
-                    TemplateType::Image {
+                    TemplateType::Image(CMSImage {
                         url: "sample.jpg".to_string(),
                         copy_asset: false,
                         size: Some(10),
-                    },
+                        alt: String::new(),
+                        caption: None,
+                        link: None,
+                        align: None,
+                    }),
                 ]),
             },
         )]);
@@ -66,5 +82,22 @@ mod test {
         assert!(gen.contains("FirstPage"));
         assert!(gen.contains("Second"));
         assert!(gen.contains("Third"));
+        assert!(gen.contains(r#"class="page""#));
+    }
+
+    #[test]
+    fn test_gen_page_multi_file() {
+        let page = CMSPage {
+            templates: Vec::from([TemplateType::Paragraph {
+                content: "First".to_string(),
+            }]),
+        };
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            output_mode: OutputMode::MultiFile,
+            ..Default::default()
+        });
+        let gen = gen_page("home", &page, &generation_dirs);
+        assert!(gen.contains(r#"id="page-home" class="page current-page""#));
+        assert!(gen.contains("First"));
     }
 }
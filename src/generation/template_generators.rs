@@ -1,28 +1,41 @@
+use chrono::{DateTime, Utc};
+
 use crate::{
     assets,
     types::{
-        cms_blog::BlogPost,
-        generation_dirs::GenerationDirs,
+        cms_blog::{slugify, BlogPost, BlogTag, CMSBlog},
+        cms_image::CMSImage,
+        generation_dirs::{GenerationDirs, ResponsiveImage},
         link_type::LinkType,
+        site_config::{BlogLayout, OutputMode},
         template_type::{TemplateType, TemplateTypeVector},
     },
 };
 
-use super::generate_code::gen_code;
+use super::{
+    escape::{escape_html, html_to_text},
+    generate_code::gen_code,
+    generate_markdown::{gen_markdown, render_markdown},
+};
 
-pub fn gen_title(templates: &Vec<TemplateType>) -> &str {
+pub fn gen_title(templates: &Vec<TemplateType>) -> String {
     match templates.get_title() {
-        Some(title) => title,
-        _ => "",
+        Some(title) => escape_html(title),
+        _ => String::new(),
     }
 }
 
-pub fn gen_navbar(templates: &Vec<TemplateType>) -> String {
+pub fn gen_navbar(templates: &Vec<TemplateType>, generation_dirs: &impl GenerationDirs) -> String {
+    let output_mode = generation_dirs.get_config().output_mode;
     match templates.get_navbar() {
         Some(paths) => {
             let paths: Vec<String> = paths
                 .iter()
-                .map(|x| format!("<a href=\"?page={x}\">{x}</a>"))
+                .map(|x| escape_html(x))
+                .map(|x| match output_mode {
+                    OutputMode::SingleFile => format!("<a href=\"?page={x}\">{x}</a>"),
+                    OutputMode::MultiFile => format!("<a href=\"{x}/\">{x}</a>"),
+                })
                 .collect();
             let paths = paths.join(" | ");
             format!("<p>{paths}</p>")
@@ -32,21 +45,65 @@ pub fn gen_navbar(templates: &Vec<TemplateType>) -> String {
 }
 
 pub fn gen_paragraph(template: &TemplateType) -> String {
-    let paragraphs = template.get_paragraph().map(|x| format!("<p>{x}</p>"));
+    let paragraphs = template
+        .get_paragraph()
+        .map(|x| format!("<p>{}</p>", escape_html(x)));
     paragraphs.unwrap_or_default()
 }
 
-pub fn gen_links(templates: &Vec<TemplateType>) -> String {
+/// Raw markup from the `Html` template, the only content that is not escaped.
+pub fn gen_html(template: &TemplateType) -> String {
+    template.get_html().cloned().unwrap_or_default()
+}
+
+/// The icon shown in front of a link, custom icons are copied from the source directory.
+fn gen_link_icon(link_type: &LinkType, generation_dirs: &impl GenerationDirs) -> String {
+    use assets::svg_images::*;
+    let svg = match link_type {
+        LinkType::Github => INVERTOCAT_SVG,
+        LinkType::GitLab => GITLAB_SVG,
+        LinkType::Codeberg => CODEBERG_SVG,
+        LinkType::LinkedIn => LINKEDIN_SVG,
+        LinkType::Mastodon => MASTODON_SVG,
+        LinkType::Email => EMAIL_SVG,
+        LinkType::Bluesky => BLUESKY_SVG,
+        LinkType::StackOverflow => STACK_OVERFLOW_SVG,
+        LinkType::Orcid => ORCID_SVG,
+        LinkType::Rss => RSS_SVG,
+        LinkType::Custom { icon: None, .. } => "",
+        LinkType::Custom {
+            icon: Some(icon), ..
+        } => {
+            if let Err(e) = generation_dirs.copy_asset(icon) {
+                log::error!("Could not copy link icon `{icon}`: {e}");
+                return String::new();
+            }
+            return format!(
+                r#"<img src="{}" alt="" class="link_image"/>"#,
+                escape_html(icon)
+            );
+        }
+    };
+    svg.to_string()
+}
+
+pub fn gen_links(templates: &Vec<TemplateType>, generation_dirs: &impl GenerationDirs) -> String {
     match templates.get_links() {
         Some(links) => {
             let links = links
                 .iter()
-                .map(|x| match x.0 {
-                    LinkType::Github => format!(
-                        r#"<a href="https://github.com/{}/">{} Github</a>"#,
-                        x.1,
-                        assets::svg_images::INVERTOCAT_SVG
-                    ),
+                .map(|(link_type, target)| {
+                    // Mastodon verifies profile links that point back with `rel="me"`
+                    let rel = match link_type {
+                        LinkType::Mastodon => r#" rel="me""#,
+                        _ => "",
+                    };
+                    format!(
+                        r#"<a href="{}"{rel}>{} {}</a>"#,
+                        escape_html(&link_type.url(target)),
+                        gen_link_icon(link_type, generation_dirs),
+                        escape_html(link_type.label())
+                    )
                 })
                 .collect::<Vec<_>>();
             let links = links.join(" | ");
@@ -69,56 +126,449 @@ pub fn gen_nr_cms_info(templates: &Vec<TemplateType>) -> String {
 pub fn gen_image(template: &TemplateType, generation_dirs: &impl GenerationDirs) -> String {
     template
         .get_image()
-        .map(|(url, copy_asset, size)| {
-            if *copy_asset {
-                let result = size
-                    .and_then(|size| Some(generation_dirs.copy_asset_img(url, size)))
-                    .or_else(|| Some(generation_dirs.copy_asset(url)));
-                if let Some(Err(_e)) = result {
-                    return Default::default();
-                }
+        .and_then(|image| gen_cms_image(image, generation_dirs))
+        .unwrap_or_default()
+}
+
+fn gen_cms_image(image: &CMSImage, generation_dirs: &impl GenerationDirs) -> Option<String> {
+    let alt = escape_html(&image.alt);
+    let img = match image.copy_asset {
+        false => format!(r#"<img src="{}" alt="{alt}"/>"#, escape_html(&image.url)),
+        true => match generation_dirs.copy_asset_img_srcset(&image.url, image.size) {
+            Ok(responsive) => gen_responsive_img(&responsive, &alt),
+            Err(e) => {
+                log::error!("Could not copy image `{}`: {e}", image.url);
+                return None;
             }
-            format!(r#"<p><img src="{}"/></p>"#, url)
+        },
+    };
+    let img = match &image.link {
+        Some(link) => format!(r#"<a href="{}">{img}</a>"#, escape_html(link)),
+        None => img,
+    };
+    let class = match image.align {
+        Some(align) => format!("image {}", align.class_name()),
+        None => "image".to_string(),
+    };
+    Some(match &image.caption {
+        Some(caption) => format!(
+            r#"<figure class="{class}">{img}<figcaption>{}</figcaption></figure>"#,
+            escape_html(caption)
+        ),
+        None => format!(r#"<p class="{class}">{img}</p>"#),
+    })
+}
+
+/// An `<img>` that lets the browser pick the smallest variant that is sharp enough.
+fn gen_responsive_img(responsive: &ResponsiveImage, alt: &str) -> String {
+    let (width, height) = (responsive.image.width, responsive.image.height);
+    let src = escape_html(&responsive.image.path);
+    let srcset = match responsive.variants.len() {
+        0 | 1 => String::new(),
+        _ => {
+            let srcset = responsive
+                .variants
+                .iter()
+                .map(|x| format!("{} {}w", escape_html(&x.path), x.width))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(r#" srcset="{srcset}" sizes="(max-width: {width}px) 100vw, {width}px""#)
+        }
+    };
+    format!(
+        r#"<img src="{src}" alt="{alt}"{srcset} width="{width}" height="{height}" loading="lazy"/>"#
+    )
+}
+
+/// Path of the page of a single post of the blog on `page_name`, relative to the generation
+/// directory.
+pub fn blog_post_path(page_name: &str, post: &BlogPost) -> String {
+    format!("{page_name}/{}/", post.slug)
+}
+
+/// The id of an element of the blog on `page_name`: a post, a page of the listing or the
+/// section of a tag. A single
+/// file contains every page, so the ids of different blogs must not collide.
+pub fn blog_element_id(kind: &str, page_name: &str, slug: &str) -> String {
+    format!("{kind}-{page_name}-{slug}")
+}
+
+/// Where the posts of the blog on `page_name` with a tag are listed: a section of the blog in
+/// a single file, otherwise a page of its own, relative to the generation directory.
+pub fn tag_path(page_name: &str, slug: &str, output_mode: OutputMode) -> String {
+    match output_mode {
+        OutputMode::SingleFile => format!("#{}", blog_element_id("tag", page_name, slug)),
+        OutputMode::MultiFile => format!("{page_name}/tags/{slug}/"),
+    }
+}
+
+fn gen_tag_link(name: &str, page_name: &str, generation_dirs: &impl GenerationDirs) -> String {
+    let output_mode = generation_dirs.get_config().output_mode;
+    let href = escape_html(&tag_path(page_name, &slugify(name), output_mode));
+    format!(r#"<a href="{href}" class="tag">#{}</a>"#, escape_html(name))
+}
+
+fn gen_post_tags(
+    post: &BlogPost,
+    page_name: &str,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    if post.tags.is_empty() {
+        return String::new();
+    }
+    let tags = post
+        .tags
+        .iter()
+        .map(|x| gen_tag_link(x, page_name, generation_dirs))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(r#"<p class="blog-post-tags">{tags}</p>"#)
+}
+
+/// Lists every tag of the blog together with the number of posts that have it.
+fn gen_tag_index(
+    tags: &[BlogTag],
+    page_name: &str,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let tags = tags
+        .iter()
+        .map(|x| {
+            let link = gen_tag_link(x.name, page_name, generation_dirs);
+            format!("<li>{link} ({})</li>", x.posts.len())
+        })
+        .collect::<Vec<_>>()
+        .concat();
+    format!(r#"<ul class="blog-tags">{tags}</ul>"#)
+}
+
+/// The sections that tag links point to in a single file. Only the one in the URL is shown.
+fn gen_tag_sections(tags: &[BlogTag], page_name: &str) -> String {
+    tags.iter()
+        .map(|tag| {
+            let posts = tag
+                .posts
+                .iter()
+                .map(|post| {
+                    let title = escape_html(&post.title);
+                    let id = escape_html(&blog_element_id("post", page_name, &post.slug));
+                    format!(r##"<li><a href="#{id}">{title}</a></li>"##)
+                })
+                .collect::<Vec<_>>()
+                .concat();
+            format!(
+                r#"<section id="{}" class="blog-tag"><h3>Posts tagged #{}</h3><ul>{posts}</ul></section>"#,
+                escape_html(&blog_element_id("tag", page_name, &tag.slug)),
+                escape_html(tag.name)
+            )
         })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The page of a single tag in multi-file mode, with the posts that have the tag in full.
+pub fn gen_tag_page(
+    tag: &BlogTag,
+    page_name: &str,
+    feed_path: Option<&str>,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    let name = escape_html(tag.name);
+    let feed = feed_path
+        .map(|x| format!(r#"<p><a href="{}">RSS</a></p>"#, escape_html(x)))
+        .unwrap_or_default();
+    let posts = tag
+        .posts
+        .iter()
+        .map(|x| gen_blog_post(x, page_name, generation_dirs))
+        .collect::<Vec<_>>()
+        .join("\n<hr>\n");
+    format!(
+        r#"
+            <div class="blog">
+            <h2>Posts tagged #{name}</h2>
+            {feed}
+            {posts}
+            </div>
+            "#
+    )
+}
+
+/// The date of a post in the time zone, format and locale of the site.
+fn gen_date(date: &DateTime<Utc>, generation_dirs: &impl GenerationDirs) -> String {
+    let config = generation_dirs.get_config();
+    let date = date.with_timezone(&config.timezone);
+    let formatted = date.format_localized(&config.dates.format, config.dates.locale());
+    format!(
+        r#"<time class="blog-post-date" datetime="{}">{}</time>"#,
+        date.to_rfc3339(),
+        escape_html(&formatted.to_string())
+    )
+}
+
+/// The date of a post, when it was last updated and by whom it was written.
+fn gen_post_meta(post: &BlogPost, generation_dirs: &impl GenerationDirs) -> String {
+    let mut meta = gen_date(&post.post_date, generation_dirs);
+    if let Some(updated) = &post.updated {
+        let updated = gen_date(updated, generation_dirs);
+        meta += &format!(r#" <span class="blog-post-updated">Updated {updated}</span>"#);
+    }
+    if let Some(author) = &post.author {
+        let author = escape_html(author);
+        meta += &format!(r#" <span class="blog-post-author">by {author}</span>"#);
+    }
+    meta
+}
+
+fn gen_post_cover(post: &BlogPost, generation_dirs: &impl GenerationDirs) -> String {
+    post.cover
+        .as_ref()
+        .and_then(|x| gen_cms_image(x, generation_dirs))
+        .map(|x| format!(r#"<div class="blog-post-cover">{x}</div>"#))
         .unwrap_or_default()
 }
 
-pub fn gen_blog_post(post: &BlogPost, generation_dirs: &impl GenerationDirs) -> Option<String> {
-    let templates = &post.templates;
-    let title = templates.get_title()?;
-    let date = templates.get_date()?;
-    let date = date.timestamp_millis();
-    let order_preserved_elements = gen_order_preserved_elements(templates, generation_dirs);
-    Some(format!(
+pub fn gen_blog_post(
+    post: &BlogPost,
+    page_name: &str,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    let title = escape_html(&post.title);
+    let title = match generation_dirs.get_config().output_mode {
+        OutputMode::SingleFile => title,
+        OutputMode::MultiFile => {
+            let href = escape_html(&blog_post_path(page_name, post));
+            format!(r#"<a href="{href}">{title}</a>"#)
+        }
+    };
+    let cover = gen_post_cover(post, generation_dirs);
+    let meta = gen_post_meta(post, generation_dirs);
+    let tags = gen_post_tags(post, page_name, generation_dirs);
+    let order_preserved_elements = gen_order_preserved_elements(&post.templates, generation_dirs);
+    let id = escape_html(&blog_element_id("post", page_name, &post.slug));
+    format!(
         r#"
-    <div class="blog-post">
+    <div id="{id}" class="blog-post">
+    {cover}
     <h2>{title}</h2>
-    <span class="blog-post-date">{date}</span>
+    {meta}
+    {tags}
     {order_preserved_elements}
     </div>
     "#
-    ))
-}
-
-pub fn gen_blog(templates: &Vec<TemplateType>, generation_dirs: &impl GenerationDirs) -> String {
-    if let Some(blog) = templates.get_blog() {
-        let mut posts = blog.posts.clone();
-        posts.sort_by(|a, b| a.post_date.cmp(&b.post_date));
-        posts.reverse();
-        let posts = posts
-            .iter()
-            .filter_map(|x| gen_blog_post(x, generation_dirs))
-            .collect::<Vec<_>>()
-            .join("\n<hr>\n");
-        return format!(
-            r#"
+    )
+}
+
+/// The posts of a blog, newest first, split into pages of `posts_per_page` posts.
+/// A blog without posts still has one, empty page.
+fn blog_pages(blog: &CMSBlog, posts_per_page: usize) -> Vec<Vec<&BlogPost>> {
+    let mut posts = blog.posts.iter().collect::<Vec<_>>();
+    posts.sort_by_key(|x| std::cmp::Reverse(x.post_date));
+    if posts_per_page == 0 || posts.is_empty() {
+        return Vec::from([posts]);
+    }
+    posts.chunks(posts_per_page).map(|x| x.to_vec()).collect()
+}
+
+/// Number of pages that the listing of a blog is split into.
+pub fn blog_page_count(blog: &CMSBlog, generation_dirs: &impl GenerationDirs) -> usize {
+    blog_pages(blog, generation_dirs.get_config().blog.posts_per_page).len()
+}
+
+/// Where page `number` of the listing of the blog on `page_name` is, counting from 1.
+/// In a single file this is a section of the page, otherwise a path relative to the
+/// generation directory.
+pub fn blog_page_path(page_name: &str, number: usize, output_mode: OutputMode) -> String {
+    match (output_mode, number) {
+        (OutputMode::SingleFile, _) => {
+            format!(
+                "#{}",
+                blog_element_id("blog-page", page_name, &number.to_string())
+            )
+        }
+        (OutputMode::MultiFile, 1) => format!("{page_name}/"),
+        (OutputMode::MultiFile, _) => format!("{page_name}/page/{number}/"),
+    }
+}
+
+fn gen_pagination(
+    page_name: &str,
+    current: usize,
+    count: usize,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    if count <= 1 {
+        return String::new();
+    }
+    let output_mode = generation_dirs.get_config().output_mode;
+    let link = |number: usize, text: &str| {
+        let href = escape_html(&blog_page_path(page_name, number, output_mode));
+        format!(r#"<a href="{href}">{text}</a>"#)
+    };
+    let mut items = Vec::new();
+    if current > 1 {
+        items.push(link(current - 1, "&laquo; Newer"));
+    }
+    for number in 1..=count {
+        items.push(match number == current {
+            true => format!(r#"<span aria-current="page">{number}</span>"#),
+            false => link(number, &number.to_string()),
+        });
+    }
+    if current < count {
+        items.push(link(current + 1, "Older &raquo;"));
+    }
+    format!(r#"<nav class="blog-pagination">{}</nav>"#, items.join(" "))
+}
+
+/// The start of the text of a post, cut after at most `length` characters.
+fn gen_excerpt(post: &BlogPost, length: usize) -> String {
+    let html = post
+        .templates
+        .iter()
+        .filter_map(|x| match x {
+            TemplateType::Paragraph { content: _ } => Some(gen_paragraph(x)),
+            TemplateType::Markdown { .. } => x.get_markdown().map(|x| render_markdown(x, |_| None)),
+            TemplateType::Html { content: _ } => Some(gen_html(x)),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut excerpt = String::new();
+    for word in html_to_text(&html).split_whitespace() {
+        let separator = usize::from(!excerpt.is_empty());
+        if excerpt.chars().count() + separator + word.chars().count() > length {
+            excerpt.push_str(" …");
+            break;
+        }
+        if separator == 1 {
+            excerpt.push(' ');
+        }
+        excerpt.push_str(word);
+    }
+    escape_html(excerpt.trim_start())
+}
+
+/// A post in the list layout: its title, date, tags and summary or an excerpt, linking to
+/// the full post.
+fn gen_blog_post_summary(
+    post: &BlogPost,
+    page_name: &str,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    let title = escape_html(&post.title);
+    let cover = gen_post_cover(post, generation_dirs);
+    let meta = gen_post_meta(post, generation_dirs);
+    let href = match generation_dirs.get_config().output_mode {
+        OutputMode::SingleFile => escape_html(&format!(
+            "#{}",
+            blog_element_id("post", page_name, &post.slug)
+        )),
+        OutputMode::MultiFile => escape_html(&blog_post_path(page_name, post)),
+    };
+    let tags = gen_post_tags(post, page_name, generation_dirs);
+    let excerpt = match &post.summary {
+        Some(summary) => escape_html(summary),
+        None => gen_excerpt(post, generation_dirs.get_config().blog.excerpt_length),
+    };
+    format!(
+        r#"
+    <div class="blog-post blog-post-summary">
+    {cover}
+    <h2><a href="{href}">{title}</a></h2>
+    {meta}
+    {tags}
+    <p class="blog-post-excerpt">{excerpt}</p>
+    <a href="{href}" class="blog-post-more">Read more</a>
+    </div>
+    "#
+    )
+}
+
+/// Page `number` of the listing of a blog, counting from 1, with links to the other pages.
+pub fn gen_blog_page(
+    blog: &CMSBlog,
+    page_name: &str,
+    number: usize,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    let config = &generation_dirs.get_config().blog;
+    let pages = blog_pages(blog, config.posts_per_page);
+    let Some(posts) = pages.get(number - 1) else {
+        return String::new();
+    };
+    let posts = posts
+        .iter()
+        .map(|x| match config.layout {
+            BlogLayout::Full => gen_blog_post(x, page_name, generation_dirs),
+            BlogLayout::List => gen_blog_post_summary(x, page_name, generation_dirs),
+        })
+        .collect::<Vec<_>>()
+        .join("\n<hr>\n");
+    let pagination = gen_pagination(page_name, number, pages.len(), generation_dirs);
+    format!("{posts}\n{pagination}")
+}
+
+/// The blog on the page `page_name`. A single file contains every page of the listing, the
+/// full posts of the list layout and the tag sections, of which only the one in the URL is
+/// shown. Otherwise only the first page is generated here, the other pages, the posts and
+/// the tags get files of their own.
+pub fn gen_blog(
+    templates: &Vec<TemplateType>,
+    page_name: &str,
+    generation_dirs: &impl GenerationDirs,
+) -> String {
+    let Some(blog) = templates.get_blog() else {
+        return String::new();
+    };
+    let config = generation_dirs.get_config();
+    let tags = blog.tags();
+    let tag_index = gen_tag_index(&tags, page_name, generation_dirs);
+    let page_count = blog_page_count(blog, generation_dirs);
+    let (listing, details, tag_sections) = match config.output_mode {
+        OutputMode::MultiFile => (
+            gen_blog_page(blog, page_name, 1, generation_dirs),
+            String::new(),
+            String::new(),
+        ),
+        OutputMode::SingleFile => {
+            let listing = (1..=page_count)
+                .map(|number| {
+                    let page = gen_blog_page(blog, page_name, number, generation_dirs);
+                    let id = blog_element_id("blog-page", page_name, &number.to_string());
+                    let id = escape_html(&id);
+                    format!(r#"<div id="{id}" class="blog-page">{page}</div>"#)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let details = match config.blog.layout {
+                BlogLayout::Full => String::new(),
+                BlogLayout::List => {
+                    let posts = blog_pages(blog, 0).concat();
+                    let posts = posts
+                        .iter()
+                        .map(|x| gen_blog_post(x, page_name, generation_dirs))
+                        .collect::<Vec<_>>()
+                        .concat();
+                    format!(r#"<div class="blog-post-details">{posts}</div>"#)
+                }
+            };
+            (listing, details, gen_tag_sections(&tags, page_name))
+        }
+    };
+    format!(
+        r#"
             <div class="blog">
-            {posts}
+            {tag_index}
+            {listing}
+            {details}
+            {tag_sections}
             </div>
             "#
-        );
-    }
-    String::new()
+    )
 }
 
 pub fn gen_order_preserved_elements(
@@ -128,13 +578,14 @@ pub fn gen_order_preserved_elements(
     templates
         .iter()
         .filter_map(|x| match x {
-            TemplateType::Image {
-                url: _,
-                copy_asset: _,
-                size: _,
-            } => Some(gen_image(x, generation_dirs)),
+            TemplateType::Image(_) => Some(gen_image(x, generation_dirs)),
             TemplateType::Paragraph { content: _ } => Some(gen_paragraph(x)),
-            TemplateType::Code { code: _ } => Some(gen_code(x)),
+            TemplateType::Code {
+                code: _,
+                language: _,
+            } => Some(gen_code(x)),
+            TemplateType::Markdown { .. } => Some(gen_markdown(x, generation_dirs)),
+            TemplateType::Html { content: _ } => Some(gen_html(x)),
             _ => None,
         })
         .collect::<Vec<_>>()
@@ -143,16 +594,29 @@ pub fn gen_order_preserved_elements(
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
 
-    use crate::types::{cms_blog::CMSBlog, generation_dirs::TempGenerationDirs};
+    use chrono::TimeZone;
+
+    use crate::types::{
+        cms_image::{CMSImage, ImageAlign},
+        generation_dirs::TempGenerationDirs,
+        site_config::{BlogConfig, DateConfig, SiteConfig},
+    };
 
     use super::*;
 
     #[test]
     fn test_blog_post() {
         let post = BlogPost {
+            slug: "testtitle".to_string(),
             post_date: Default::default(),
+            title: "testtitle".to_string(),
+            updated: None,
+            author: None,
+            summary: None,
+            cover: None,
+            tags: Vec::new(),
+            draft: false,
             templates: Vec::from([
                 TemplateType::Title {
                     title: "testtitle".to_string(),
@@ -165,15 +629,169 @@ mod test {
                 },
             ]),
         };
-        let gen = gen_blog_post(&post, &TempGenerationDirs::default()).unwrap();
+        let gen = gen_blog_post(&post, "blog", &TempGenerationDirs::default());
         assert!(gen.contains("testtest"));
         assert!(gen.contains("testtitle"));
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            output_mode: OutputMode::MultiFile,
+            ..Default::default()
+        });
+        let gen = gen_blog_post(&post, "blog", &generation_dirs);
+        assert!(gen.contains(r#"<a href="blog/testtitle/">testtitle</a>"#));
+        let gen = gen_blog_post(&post, "news", &generation_dirs);
+        assert!(gen.contains(r#"<a href="news/testtitle/">testtitle</a>"#));
+    }
+
+    #[test]
+    fn test_gen_date() {
+        let date = Utc.with_ymd_and_hms(2023, 12, 31, 23, 30, 0).unwrap();
+        assert_eq!(
+            gen_date(&date, &TempGenerationDirs::default()),
+            r#"<time class="blog-post-date" datetime="2023-12-31T23:30:00+00:00">December 31, 2023</time>"#
+        );
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            timezone: chrono_tz::Europe::Berlin,
+            dates: DateConfig {
+                format: "%A, %-d. %B %Y <%H:%M>".to_string(),
+                locale: "de_DE".to_string(),
+                localize: false,
+            },
+            ..Default::default()
+        });
+        assert_eq!(
+            gen_date(&date, &generation_dirs),
+            r#"<time class="blog-post-date" datetime="2024-01-01T00:30:00+01:00">Montag, 1. Januar 2024 &lt;00:30&gt;</time>"#
+        );
+    }
+
+    #[test]
+    fn test_order_preserved_elements() {
+        let templates = Vec::from([
+            TemplateType::Paragraph {
+                content: "first".to_string(),
+            },
+            TemplateType::Markdown {
+                content: "*second*".to_string(),
+                images: Default::default(),
+            },
+            TemplateType::Code {
+                code: "third".to_string(),
+                language: Some("rust".to_string()),
+            },
+        ]);
+        let gen = gen_order_preserved_elements(&templates, &TempGenerationDirs::default());
+        let first = gen.find("first").unwrap();
+        let second = gen.find("<em>second</em>").unwrap();
+        let third = gen.find("third").unwrap();
+        assert!(first < second && second < third);
+    }
+
+    fn numbered_blog(count: u32) -> CMSBlog {
+        let posts = (1..=count)
+            .map(|day| BlogPost {
+                slug: format!("post-{day}"),
+                post_date: chrono::Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+                title: format!("Post {day}"),
+                updated: None,
+                author: None,
+                summary: None,
+                cover: None,
+                tags: Vec::new(),
+                draft: false,
+                templates: Vec::from([
+                    TemplateType::Title {
+                        title: format!("Post {day}"),
+                    },
+                    TemplateType::Date {
+                        date: chrono::Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+                    },
+                    TemplateType::Paragraph {
+                        content: format!("Content of <post> {day}, which is long enough"),
+                    },
+                ]),
+            })
+            .collect();
+        CMSBlog {
+            dir: "blog_files/".to_string(),
+            next_publication: None,
+            posts,
+        }
+    }
+
+    fn blog_config(output_mode: OutputMode, blog: BlogConfig) -> TempGenerationDirs {
+        TempGenerationDirs::with_config(SiteConfig {
+            output_mode,
+            blog,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_blog_pagination() {
+        let blog = numbered_blog(3);
+        let templates = Vec::from([TemplateType::Blog(blog.clone())]);
+        let paginated = BlogConfig {
+            posts_per_page: 2,
+            ..Default::default()
+        };
+        let generation_dirs = blog_config(OutputMode::SingleFile, paginated.clone());
+        let gen = gen_blog(&templates, "blog", &generation_dirs);
+        assert!(gen.contains(r#"<div id="blog-page-blog-2" class="blog-page">"#));
+        assert!(gen.contains(r##"<a href="#blog-page-blog-2">Older &raquo;</a>"##));
+        assert!(gen.contains(r#"<span aria-current="page">2</span>"#));
+        assert!(gen.find("Post 3").unwrap() < gen.find("Post 1").unwrap());
+
+        let generation_dirs = blog_config(OutputMode::MultiFile, paginated);
+        assert_eq!(blog_page_count(&blog, &generation_dirs), 2);
+        let gen = gen_blog(&templates, "blog", &generation_dirs);
+        assert!(gen.contains("Post 2") && !gen.contains("Post 1"));
+        assert!(gen.contains(r#"<a href="blog/page/2/">2</a>"#));
+        let page = gen_blog_page(&blog, "blog", 2, &generation_dirs);
+        assert!(page.contains("Post 1") && !page.contains("Post 2"));
+        assert!(page.contains(r#"<a href="blog/">&laquo; Newer</a>"#));
+        assert_eq!(gen_blog_page(&blog, "blog", 3, &generation_dirs), "");
+
+        let generation_dirs = TempGenerationDirs::default();
+        let gen = gen_blog(&templates, "blog", &generation_dirs);
+        assert!(!gen.contains("blog-pagination"));
+        assert_eq!(blog_page_count(&numbered_blog(0), &generation_dirs), 1);
+    }
+
+    #[test]
+    fn test_blog_list_layout() {
+        let blog = numbered_blog(2);
+        let templates = Vec::from([TemplateType::Blog(blog)]);
+        let list = BlogConfig {
+            layout: BlogLayout::List,
+            excerpt_length: 24,
+            ..Default::default()
+        };
+        let generation_dirs = blog_config(OutputMode::SingleFile, list.clone());
+        let gen = gen_blog(&templates, "blog", &generation_dirs);
+        assert!(gen.contains(r##"<h2><a href="#post-blog-post-2">Post 2</a></h2>"##));
+        assert!(gen.contains(r#"<p class="blog-post-excerpt">Content of &lt;post&gt; 2, …</p>"#));
+        assert!(gen.contains(r#"<div class="blog-post-details">"#));
+        assert!(gen.contains(r#"<div id="post-blog-post-2" class="blog-post">"#));
+
+        let generation_dirs = blog_config(OutputMode::MultiFile, list);
+        let gen = gen_blog(&templates, "blog", &generation_dirs);
+        assert!(gen.contains(r#"<a href="blog/post-2/" class="blog-post-more">"#));
+        assert!(!gen.contains("blog-post-details"));
+        assert!(!gen.contains("which is long enough"));
     }
 
     #[test]
     fn test_blog() {
         let post = BlogPost {
+            slug: "testtitle".to_string(),
             post_date: Default::default(),
+            title: "testtitle".to_string(),
+            updated: None,
+            author: None,
+            summary: None,
+            cover: None,
+            tags: Vec::new(),
+            draft: false,
             templates: Vec::from([
                 TemplateType::Title {
                     title: "testtitle".to_string(),
@@ -187,12 +805,74 @@ mod test {
             ]),
         };
         let blog = CMSBlog {
+            dir: "blog_files/".to_string(),
+            next_publication: None,
             posts: Vec::from([post]),
         };
         let templates = Vec::from([TemplateType::Blog(blog)]);
-        let gen = gen_blog(&templates, &TempGenerationDirs::default());
+        let gen = gen_blog(&templates, "blog", &TempGenerationDirs::default());
         assert!(gen.contains("testtest"));
         assert!(gen.contains("testtitle"));
+        assert!(!gen.contains("blog-tags"));
+    }
+
+    #[test]
+    fn test_blog_tags() {
+        let post = BlogPost {
+            slug: "testtitle".to_string(),
+            post_date: Default::default(),
+            title: "testtitle".to_string(),
+            updated: None,
+            author: None,
+            summary: None,
+            cover: None,
+            tags: Vec::from(["Rust".to_string(), "C & C++".to_string()]),
+            draft: false,
+            templates: Vec::from([
+                TemplateType::Title {
+                    title: "testtitle".to_string(),
+                },
+                TemplateType::Date {
+                    date: chrono::Utc::now(),
+                },
+            ]),
+        };
+        let blog = CMSBlog {
+            dir: "blog_files/".to_string(),
+            next_publication: None,
+            posts: Vec::from([post]),
+        };
+        let templates = Vec::from([TemplateType::Blog(blog.clone())]);
+        let gen = gen_blog(&templates, "blog", &TempGenerationDirs::default());
+        assert!(gen.contains(r##"<li><a href="#tag-blog-rust" class="tag">#Rust</a> (1)</li>"##));
+        assert!(gen.contains(r##"<a href="#tag-blog-c-c" class="tag">#C &amp; C++</a>"##));
+        assert!(gen.contains(r#"<section id="tag-blog-rust" class="blog-tag">"#));
+        let news = gen_blog(&templates, "news", &TempGenerationDirs::default());
+        assert!(news.contains(r##"<li><a href="#tag-news-rust" class="tag">#Rust</a> (1)</li>"##));
+        assert!(news.contains(r#"<section id="tag-news-rust" class="blog-tag">"#));
+        assert!(!news.contains("tag-blog-"));
+        assert!(gen.contains(r##"<li><a href="#post-blog-testtitle">testtitle</a></li>"##));
+
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            output_mode: OutputMode::MultiFile,
+            ..Default::default()
+        });
+        let gen = gen_blog(&templates, "blog", &generation_dirs);
+        assert!(gen.contains(r#"<a href="blog/tags/rust/" class="tag">#Rust</a>"#));
+        assert!(!gen.contains("<section"));
+        let gen = gen_blog(&templates, "news", &generation_dirs);
+        assert!(gen.contains(r#"<a href="news/tags/rust/" class="tag">#Rust</a>"#));
+        let tags = blog.tags();
+        let page = gen_tag_page(
+            &tags[1],
+            "news",
+            Some("news/tags/rust/feed.xml"),
+            &generation_dirs,
+        );
+        assert!(page.contains("Posts tagged #Rust"));
+        assert!(page.contains(r#"<a href="news/tags/rust/feed.xml">RSS</a>"#));
+        assert!(page.contains(r#"id="post-news-testtitle""#));
+        assert!(page.contains(r#"<a href="news/testtitle/">testtitle</a>"#));
     }
     #[test]
     fn test_gen_title() {
@@ -207,9 +887,15 @@ mod test {
         let test = vec![TemplateType::Navbar {
             paths: Vec::from(["first".to_string(), "second".to_string()]),
         }];
-        let navbar = gen_navbar(&test);
-        assert!(navbar.contains("first"));
+        let navbar = gen_navbar(&test, &TempGenerationDirs::default());
+        assert!(navbar.contains(r#"href="?page=first""#));
         assert!(navbar.contains("second"));
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            output_mode: OutputMode::MultiFile,
+            ..Default::default()
+        });
+        let navbar = gen_navbar(&test, &generation_dirs);
+        assert!(navbar.contains(r#"href="first/""#));
     }
 
     #[test]
@@ -221,18 +907,78 @@ mod test {
         assert!(paragraphs.contains("first"));
     }
 
+    #[test]
+    fn test_escaping() {
+        let test = vec![
+            TemplateType::Title {
+                title: "Tom & Jerry".to_string(),
+            },
+            TemplateType::Navbar {
+                paths: Vec::from([r#"a"><script>"#.to_string()]),
+            },
+        ];
+        assert_eq!(gen_title(&test), "Tom &amp; Jerry");
+        let navbar = gen_navbar(&test, &TempGenerationDirs::default());
+        assert!(!navbar.contains("<script>"));
+        let test = TemplateType::Paragraph {
+            content: "if a < b".to_string(),
+        };
+        assert_eq!(gen_paragraph(&test), "<p>if a &lt; b</p>");
+    }
+
+    #[test]
+    fn test_gen_html() {
+        let test = TemplateType::Html {
+            content: "<b>first</b><br/>".to_string(),
+        };
+        assert_eq!(gen_html(&test), "<b>first</b><br/>");
+        let test = TemplateType::Paragraph {
+            content: "first".to_string(),
+        };
+        assert_eq!(gen_html(&test), String::new());
+    }
+
     #[test]
     fn test_gen_links() {
         let test = vec![TemplateType::Links {
-            links: HashMap::from([(LinkType::Github, "first".to_string())]),
+            links: Vec::from([(LinkType::Github, "first".to_string())]),
         }];
-        let links = gen_links(&test);
+        let links = gen_links(&test, &TempGenerationDirs::default());
         assert!(links.contains("first"));
     }
 
+    #[test]
+    fn test_gen_link_types() {
+        let generation_dirs = TempGenerationDirs::default();
+        let test = vec![TemplateType::Links {
+            links: Vec::from([
+                (LinkType::Mastodon, "@me@example.social".to_string()),
+                (
+                    LinkType::Custom {
+                        label: "Me & co".to_string(),
+                        icon: Some("sample.jpg".to_string()),
+                    },
+                    "https://example.com/?a=1&b=2".to_string(),
+                ),
+                (LinkType::Email, "me@example.com".to_string()),
+            ]),
+        }];
+        let links = gen_links(&test, &generation_dirs);
+        let mastodon = links.find(r#"<a href="https://example.social/@me" rel="me">"#);
+        let custom =
+            links.find(r#"<a href="https://example.com/?a=1&amp;b=2"><img src="sample.jpg""#);
+        let email = links.find(r#"<a href="mailto:me@example.com">"#);
+        assert!(mastodon.unwrap() < custom.unwrap());
+        assert!(custom.unwrap() < email.unwrap());
+        assert!(links.contains("Me &amp; co</a>"));
+        assert!(generation_dirs.in_gen("sample.jpg").exists());
+    }
+
     #[test]
     fn test_gen_info() {
-        let test = vec![TemplateType::NRCMSInfo { text: "first" }];
+        let test = vec![TemplateType::NRCMSInfo {
+            text: "first".to_string(),
+        }];
         let info = gen_nr_cms_info(&test);
         assert!(info.contains("first"));
     }
@@ -241,38 +987,78 @@ mod test {
     fn test_gen_image() {
         let generation_dirs = TempGenerationDirs::default();
 
-        let test = TemplateType::Image {
+        let test = TemplateType::Image(CMSImage {
             url: "sample.jpg".to_string(),
             copy_asset: true,
             size: Some(200),
-        };
+            alt: "Me".to_string(),
+            caption: None,
+            link: None,
+            align: None,
+        });
         let image = gen_image(&test, &generation_dirs);
-        assert!(image.contains("sample.jpg"));
+        assert!(image.contains(r#"src="sample.jpg""#));
+        assert!(image.contains(r#"srcset="sample-100.jpg 100w, sample.jpg 200w"#));
+        assert!(image.contains(r#"sizes="(max-width: 200px) 100vw, 200px""#));
+        assert!(image.contains(r#"width="200""#));
+        assert!(image.contains(r#"loading="lazy""#));
+        assert!(image.contains(r#"alt="Me""#));
+        assert!(image.starts_with(r#"<p class="image"><img"#));
 
-        let test = TemplateType::Image {
+        let test = TemplateType::Image(CMSImage {
             url: "sample.jpg".to_string(),
             copy_asset: false,
             size: Some(200),
-        };
+            alt: "Me".to_string(),
+            caption: None,
+            link: None,
+            align: None,
+        });
         let image = gen_image(&test, &generation_dirs);
         assert!(image.contains("sample.jpg"));
     }
 
+    #[test]
+    fn test_gen_image_options() {
+        let test = TemplateType::Image(CMSImage {
+            url: "data:image/png;base64,".to_string(),
+            copy_asset: false,
+            size: None,
+            alt: "A \"quote\"".to_string(),
+            caption: Some("<Caption>".to_string()),
+            link: Some("https://example.com/?a=1&b=2".to_string()),
+            align: Some(ImageAlign::Center),
+        });
+        let image = gen_image(&test, &TempGenerationDirs::default());
+        assert_eq!(
+            image,
+            r#"<figure class="image image-center"><a href="https://example.com/?a=1&amp;b=2"><img src="data:image/png;base64," alt="A &quot;quote&quot;"/></a><figcaption>&lt;Caption&gt;</figcaption></figure>"#
+        );
+    }
+
     #[test]
     fn test_image_no_exist() {
-        let test = TemplateType::Image {
+        let test = TemplateType::Image(CMSImage {
             url: "sample_no_exist.jpg".to_string(),
             copy_asset: true,
             size: Some(200),
-        };
+            alt: String::new(),
+            caption: None,
+            link: None,
+            align: None,
+        });
         let image = gen_image(&test, &TempGenerationDirs::default());
         assert_eq!(image, String::new());
 
-        let test = TemplateType::Image {
+        let test = TemplateType::Image(CMSImage {
             url: "sample_no_exist.jpg".to_string(),
             copy_asset: true,
             size: None,
-        };
+            alt: String::new(),
+            caption: None,
+            link: None,
+            align: None,
+        });
         let image = gen_image(&test, &TempGenerationDirs::default());
         assert_eq!(image, String::new());
     }
@@ -281,7 +1067,13 @@ mod test {
         let test = Vec::<TemplateType>::new();
         assert_eq!(gen_title(&test), String::new());
         assert_eq!(gen_nr_cms_info(&test), String::new());
-        assert_eq!(gen_links(&test), String::new());
-        assert_eq!(gen_navbar(&test), String::new());
+        assert_eq!(
+            gen_links(&test, &TempGenerationDirs::default()),
+            String::new()
+        );
+        assert_eq!(
+            gen_navbar(&test, &TempGenerationDirs::default()),
+            String::new()
+        );
     }
 }
@@ -1,73 +1,282 @@
-use std::io::Cursor;
+use std::{io::Cursor, path::Path};
 
 use base64::{engine::general_purpose, Engine as _};
 
-use image::imageops::FilterType;
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageOutputFormat, Rgb, RgbImage};
 
-pub fn get_img_b64_size(
-    path: &std::path::Path,
-    size: Option<u32>,
-) -> Result<usize, Box<dyn std::error::Error>> {
-    let img_b64 = get_img_as_b64_url(path, size)?;
-    Ok(img_b64.as_bytes().len())
+use crate::types::site_config::{ImageConfig, ImageEncoding, ResizeFilter};
+
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+
+fn filter_type(filter: ResizeFilter) -> FilterType {
+    match filter {
+        ResizeFilter::Nearest => FilterType::Nearest,
+        ResizeFilter::Triangle => FilterType::Triangle,
+        ResizeFilter::CatmullRom => FilterType::CatmullRom,
+        ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+/// Reads the EXIF orientation (1 to 8) of a JPEG, if it has one.
+fn exif_orientation(bytes: &[u8]) -> Option<u16> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut i = 2;
+    // Walk the segments until the image data starts, looking for the APP1 segment with the EXIF data.
+    while i + 4 <= bytes.len() && bytes[i] == 0xFF && bytes[i + 1] != 0xDA {
+        let length = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        let segment = bytes.get(i + 4..i + 2 + length)?;
+        if bytes[i + 1] == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return tiff_orientation(tiff);
+            }
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(match little_endian {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    };
+    let u32_at = |offset: usize| {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(match little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    };
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|x| ifd + 2 + x * 12)
+        .find(|entry| u16_at(*entry) == Some(EXIF_ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+}
+
+/// Rotates and mirrors an image so that it is shown upright, as cameras only record how they were held.
+fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let img = image::io::Reader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .decode()?;
+    Ok(match exif_orientation(bytes) {
+        Some(orientation) => apply_orientation(img, orientation),
+        None => img,
+    })
+}
+
+/// Shrinks an image to fit into a `size` x `size` box, keeping its aspect ratio.
+/// Images that already fit are not enlarged.
+fn shrink(img: DynamicImage, size: u32, filter: ResizeFilter) -> DynamicImage {
+    if img.width() <= size && img.height() <= size {
+        return img;
+    }
+    img.resize(size, size, filter_type(filter))
+}
+
+/// Drops the alpha channel by putting the image on a white background,
+/// instead of letting transparent pixels turn black.
+fn flatten(img: &DynamicImage) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u16 * a as u16 + 255 * (255 - a as u16)) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+fn encode(
+    img: &DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut buf: Vec<u8> = Vec::new();
+    match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(flatten(img))
+            .write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Jpeg(quality))?,
+        ImageFormat::Png => img.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)?,
+        #[cfg(feature = "webp")]
+        ImageFormat::WebP => {
+            use image::{
+                codecs::webp::{WebPEncoder, WebPQuality},
+                ImageEncoder,
+            };
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_with_quality(&mut buf, WebPQuality::lossy(quality)).write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )?;
+        }
+        _ => return Err(format!("cannot encode images as {format:?}").into()),
+    }
+    Ok(buf)
+}
+
+/// The format a resized copy of `source` is written in.
+pub fn output_format(source: &Path, encoding: ImageEncoding) -> ImageFormat {
+    match encoding {
+        ImageEncoding::Auto => match ImageFormat::from_path(source) {
+            Ok(ImageFormat::Jpeg) => ImageFormat::Jpeg,
+            Ok(ImageFormat::WebP) if cfg!(feature = "webp") => ImageFormat::WebP,
+            _ => ImageFormat::Png,
+        },
+        ImageEncoding::Jpeg => ImageFormat::Jpeg,
+        ImageEncoding::Png => ImageFormat::Png,
+        ImageEncoding::Webp => ImageFormat::WebP,
+    }
 }
 
 pub fn get_img_as_b64_url(
-    path: &std::path::Path,
-    size: Option<u32>,
+    path: &Path,
+    size: u32,
+    config: &ImageConfig,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let size = size.unwrap_or(200);
-    let img = image::io::Reader::open(path)?.decode()?;
-    let img = img.resize(size, size, FilterType::Nearest);
-    let mut buf: Vec<u8> = Vec::new();
-    img.write_to(
-        &mut Cursor::new(&mut buf),
-        image::ImageOutputFormat::Jpeg(70),
-    )?;
+    let img = decode(&std::fs::read(path)?)?;
+    let img = shrink(img, size, config.filter);
+    let format = match config.format {
+        ImageEncoding::Auto if img.color().has_alpha() => ImageFormat::Png,
+        ImageEncoding::Auto => ImageFormat::Jpeg,
+        _ => output_format(path, config.format),
+    };
+    let buf = encode(&img, format, config.quality)?;
     let b64 = general_purpose::STANDARD.encode(&buf);
-    let b64 = format!("data:image/jpg;base64,{b64}");
+    let b64 = format!("data:{};base64,{b64}", format.to_mime_type());
     Ok(b64)
 }
 
+/// Writes a resized copy of `source` to `target`, in the format given by the extension of `target`.
 pub fn resize_image(
-    source: &std::path::Path,
-    target: &std::path::Path,
+    source: &Path,
+    target: &Path,
     size: u32,
+    config: &ImageConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let img = image::io::Reader::open(source)?.decode()?;
-    let img = img.resize(size, size, FilterType::Nearest);
+    let img = decode(&std::fs::read(source)?)?;
+    let img = shrink(img, size, config.filter);
+    let buf = encode(&img, ImageFormat::from_path(target)?, config.quality)?;
     let parent = target.parent().ok_or(std::io::Error::new(
         std::io::ErrorKind::NotFound,
         "cannot find parent",
     ))?;
     std::fs::create_dir_all(parent)?;
-    img.save(target)?;
+    std::fs::write(target, buf)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use image::{GenericImageView, Rgba, RgbaImage};
+
     use super::*;
 
+    fn nearest() -> ImageConfig {
+        ImageConfig {
+            filter: ResizeFilter::Nearest,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_resize() {
         let sample_img_path = std::path::Path::new("sample/sample.jpg");
         std::fs::create_dir_all("gen/").unwrap();
         let target = std::path::Path::new("gen/sample.jpg");
-        resize_image(sample_img_path, target, 100).unwrap();
+        resize_image(sample_img_path, target, 100, &Default::default()).unwrap();
+        let (width, height) = image::open(target).unwrap().dimensions();
+        assert_eq!(width.max(height), 100);
     }
 
     #[test]
     fn test_get_img_as_b64() {
         let sample_img_path: &std::path::Path = std::path::Path::new("sample/sample.jpg");
-        let b64 = get_img_as_b64_url(sample_img_path, Some(10)).unwrap();
-        assert_eq!(b64, "data:image/jpg;base64,/9j/4AAQSkZJRgABAgAAAQABAAD/wAARCAAKAAoDAREAAhEBAxEB/9sAQwAKBwcIBwYKCAgICwoKCw4YEA4NDQ4dFRYRGCMfJSQiHyIhJis3LyYpNCkhIjBBMTQ5Oz4+PiUuRElDPEg3PT47/9sAQwEKCwsODQ4cEBAcOygiKDs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7/8QAHwAAAQUBAQEBAQEAAAAAAAAAAAECAwQFBgcICQoL/8QAtRAAAgEDAwIEAwUFBAQAAAF9AQIDAAQRBRIhMUEGE1FhByJxFDKBkaEII0KxwRVS0fAkM2JyggkKFhcYGRolJicoKSo0NTY3ODk6Q0RFRkdISUpTVFVWV1hZWmNkZWZnaGlqc3R1dnd4eXqDhIWGh4iJipKTlJWWl5iZmqKjpKWmp6ipqrKztLW2t7i5usLDxMXGx8jJytLT1NXW19jZ2uHi4+Tl5ufo6erx8vP09fb3+Pn6/8QAHwEAAwEBAQEBAQEBAQAAAAAAAAECAwQFBgcICQoL/8QAtREAAgECBAQDBAcFBAQAAQJ3AAECAxEEBSExBhJBUQdhcRMiMoEIFEKRobHBCSMzUvAVYnLRChYkNOEl8RcYGRomJygpKjU2Nzg5OkNERUZHSElKU1RVVldYWVpjZGVmZ2hpanN0dXZ3eHl6goOEhYaHiImKkpOUlZaXmJmaoqOkpaanqKmqsrO0tba3uLm6wsPExcbHyMnK0tPU1dbX2Nna4uPk5ebn6Onq8vP09fb3+Pn6/9oADAMBAAIRAxEAPwCDTtZ1QaykkOoSx2P2pzBa+a0QYNJnjHyMDIzKzMCAAccjAm5aSKg8Wa843RxXrIeVLXVySR2yVcA/gAPQCgCVoYn1IM8aMySXG0lQSuHGMemO1IZBaajfJZwol7cKqxqABKwAGPrTA//Z");
+        let b64 = get_img_as_b64_url(sample_img_path, 10, &nearest()).unwrap();
+        assert_eq!(b64, "data:image/jpeg;base64,/9j/4AAQSkZJRgABAgAAAQABAAD/wAARCAAKAAoDAREAAhEBAxEB/9sAQwAKBwcIBwYKCAgICwoKCw4YEA4NDQ4dFRYRGCMfJSQiHyIhJis3LyYpNCkhIjBBMTQ5Oz4+PiUuRElDPEg3PT47/9sAQwEKCwsODQ4cEBAcOygiKDs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7Ozs7/8QAHwAAAQUBAQEBAQEAAAAAAAAAAAECAwQFBgcICQoL/8QAtRAAAgEDAwIEAwUFBAQAAAF9AQIDAAQRBRIhMUEGE1FhByJxFDKBkaEII0KxwRVS0fAkM2JyggkKFhcYGRolJicoKSo0NTY3ODk6Q0RFRkdISUpTVFVWV1hZWmNkZWZnaGlqc3R1dnd4eXqDhIWGh4iJipKTlJWWl5iZmqKjpKWmp6ipqrKztLW2t7i5usLDxMXGx8jJytLT1NXW19jZ2uHi4+Tl5ufo6erx8vP09fb3+Pn6/8QAHwEAAwEBAQEBAQEBAQAAAAAAAAECAwQFBgcICQoL/8QAtREAAgECBAQDBAcFBAQAAQJ3AAECAxEEBSExBhJBUQdhcRMiMoEIFEKRobHBCSMzUvAVYnLRChYkNOEl8RcYGRomJygpKjU2Nzg5OkNERUZHSElKU1RVVldYWVpjZGVmZ2hpanN0dXZ3eHl6goOEhYaHiImKkpOUlZaXmJmaoqOkpaanqKmqsrO0tba3uLm6wsPExcbHyMnK0tPU1dbX2Nna4uPk5ebn6Onq8vP09fb3+Pn6/9oADAMBAAIRAxEAPwCDTtZ1QaykkOoSx2P2pzBa+a0QYNJnjHyMDIzKzMCAAccjAm5aSKg8Wa843RxXrIeVLXVySR2yVcA/gAPQCgCVoYn1IM8aMySXG0lQSuHGMemO1IZBaajfJZwol7cKqxqABKwAGPrTA//Z");
     }
 
     #[test]
     fn test_get_img_b64_size() {
         let sample_img_path = std::path::Path::new("sample/sample.jpg");
-        let size = get_img_b64_size(sample_img_path, Some(10)).unwrap();
-        assert_eq!(size, 994);
+        let size = get_img_as_b64_url(sample_img_path, 10, &nearest())
+            .unwrap()
+            .len();
+        assert_eq!(size, 995);
+    }
+
+    #[test]
+    fn test_transparent_images() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let logo = tmp_dir.path().join("logo.png");
+        RgbaImage::from_pixel(40, 20, Rgba([0, 0, 0, 0]))
+            .save(&logo)
+            .unwrap();
+
+        let b64 = get_img_as_b64_url(&logo, 10, &Default::default()).unwrap();
+        assert!(b64.starts_with("data:image/png;base64,"));
+
+        let target = tmp_dir.path().join("logo.jpg");
+        resize_image(&logo, &target, 10, &Default::default()).unwrap();
+        let img = image::open(&target).unwrap();
+        assert_eq!(img.dimensions(), (10, 5));
+        assert!(img.to_rgb8().pixels().all(|x| x.0.iter().all(|c| *c > 240)));
+    }
+
+    #[test]
+    fn test_output_format() {
+        let auto = ImageEncoding::Auto;
+        assert_eq!(output_format(Path::new("a.JPG"), auto), ImageFormat::Jpeg);
+        assert_eq!(output_format(Path::new("a.gif"), auto), ImageFormat::Png);
+        assert_eq!(
+            output_format(Path::new("a.jpg"), ImageEncoding::Png),
+            ImageFormat::Png
+        );
+    }
+
+    #[test]
+    fn test_exif_orientation() {
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 2))
+            .write_to(&mut Cursor::new(&mut jpeg), ImageOutputFormat::Jpeg(90))
+            .unwrap();
+        assert_eq!(exif_orientation(&jpeg), None);
+
+        // Little endian TIFF with a single IFD entry: orientation 6, rotated by 90 degrees.
+        let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x01\0".to_vec();
+        exif.extend([0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0]);
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend(((exif.len() + 2) as u16).to_be_bytes());
+        app1.extend(exif);
+        jpeg.splice(2..2, app1);
+
+        assert_eq!(exif_orientation(&jpeg), Some(6));
+        assert_eq!(decode(&jpeg).unwrap().dimensions(), (2, 4));
     }
 }
@@ -2,14 +2,20 @@
 
 use crate::{
     generation::generate_website::generate_website,
-    types::{generation_dirs::StandardGenerationDirs, program_args::ProgramArgs},
+    types::{
+        generation_dirs::StandardGenerationDirs,
+        program_args::{Command, ProgramArgs},
+    },
 };
 use clap::Parser;
+use std::sync::mpsc::Receiver;
 
 mod assets;
+mod build_cache;
 mod generation;
 mod img_handling;
 mod parsing;
+mod server;
 mod types;
 mod watcher;
 
@@ -19,6 +25,19 @@ fn init_logging(run_args: &ProgramArgs) {
     env_logger::init_from_env(env);
 }
 
+/// Sends `true` on the returned cancellation token when Ctrl-C is pressed.
+fn cancel_on_ctrl_c() -> Receiver<bool> {
+    let (cancel, cancellation_token) = std::sync::mpsc::channel();
+    let result = ctrlc::set_handler(move || {
+        log::info!("Shutting down.");
+        let _ = cancel.send(true);
+    });
+    if let Err(e) = result {
+        log::warn!("Could not handle Ctrl-C: {e}");
+    }
+    cancellation_token
+}
+
 fn main() {
     let run_args = ProgramArgs::parse();
     //let run_args = parse_args();
@@ -28,13 +47,31 @@ fn main() {
     log::debug!("Source Directory: {}", &run_args.source_dir);
     log::debug!("Generation Directory: {}", &run_args.generation_dir);
     log::debug!("Max Log Level: {}", &run_args.max_log_level);
-    if run_args.watch {
+    log::debug!("Output Mode: {:?}", &run_args.output_mode);
+    log::debug!("Base URL: {:?}", &run_args.base_url);
+    log::debug!("Drafts: {}", &run_args.drafts);
+    let command = run_args.command.clone();
+    let watch = run_args.watch;
+    let generation_dirs = match StandardGenerationDirs::try_from(run_args) {
+        Ok(generation_dirs) => generation_dirs,
+        Err(e) => {
+            log::error!("Could not load configuration: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(Command::Serve { port, address }) = command {
+        log::info!("Running in serve mode.");
+        let cancellation_token = cancel_on_ctrl_c();
+        if let Err(e) = server::serve(generation_dirs, &address, port, Some(cancellation_token)) {
+            log::error!("Could not start server: {e}");
+        }
+    } else if watch {
         log::info!("Running in watch mode.");
-        if let Err(e) = watcher::watch(StandardGenerationDirs::from(run_args), None) {
+        if let Err(e) = watcher::watch(generation_dirs, Some(cancel_on_ctrl_c())) {
             log::error!("Could not initialize watcher: {e}");
         }
-    } else {
-        generate_website(&StandardGenerationDirs::from(run_args));
+    } else if generate_website(&generation_dirs).is_err() {
+        std::process::exit(1);
     }
 }
 
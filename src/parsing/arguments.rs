@@ -0,0 +1,257 @@
+use std::str::FromStr;
+
+use super::{
+    diagnostics::{Diagnostics, ParseError},
+    tokenizer::{split_args, Arg},
+};
+
+/// Splits `key=value` into the key and the raw value, if the argument is named.
+/// Keys consist of ASCII letters, digits, `_` and `-`. A literal `=` can be escaped as `\=`.
+pub fn split_key(raw: &str) -> Option<(&str, &str)> {
+    let raw = raw.trim_start();
+    let key_len = raw.find(|x: char| !(x.is_ascii_alphanumeric() || x == '_' || x == '-'))?;
+    if key_len == 0 || !raw[key_len..].starts_with('=') {
+        return None;
+    }
+    Some((&raw[..key_len], raw[key_len + 1..].trim()))
+}
+
+fn single_arg(raw: &str) -> Result<Arg<'_>, ParseError<'_>> {
+    let mut args = split_args(raw, '|')?;
+    Ok(args.pop().unwrap_or(Arg {
+        raw,
+        value: String::new(),
+    }))
+}
+
+/// The arguments of a template. Arguments separated by `|` can be named with `key=value`,
+/// e.g. `{{Image|src=sample.jpg|size=200}}`. Unnamed arguments are additionally split on `,`
+/// and assigned to the parameters in order, so `{{Image|sample.jpg,200}}` means the same.
+pub struct Arguments<'a, 'p> {
+    template: &'p str,
+    params: &'p [&'p str],
+    positional: Vec<Arg<'a>>,
+    named: Vec<(&'a str, Arg<'a>)>,
+}
+
+impl<'a, 'p> Arguments<'a, 'p> {
+    fn split(
+        content: &'a str,
+        template: &'p str,
+        params: &'p [&'p str],
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Arguments<'a, 'p>, ParseError<'a>> {
+        let mut arguments = Arguments {
+            template,
+            params,
+            positional: Vec::new(),
+            named: Vec::new(),
+        };
+        for segment in split_args(content, '|')? {
+            let Some((key, value)) = split_key(segment.raw) else {
+                arguments.positional.extend(split_args(segment.raw, ',')?);
+                continue;
+            };
+            if !params.contains(&key) {
+                diagnostics.warning(
+                    format!(
+                        "unknown argument `{key}` for `{template}`, expected one of {}",
+                        params
+                            .iter()
+                            .map(|x| format!("`{x}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    key,
+                );
+                continue;
+            }
+            if arguments.named.iter().any(|x| x.0 == key) {
+                diagnostics.warning(format!("argument `{key}` is given more than once"), key);
+                continue;
+            }
+            arguments.named.push((key, single_arg(value)?));
+        }
+        Ok(arguments)
+    }
+
+    /// Parses the arguments of a template that takes the parameters `params`.
+    pub fn parse(
+        content: &'a str,
+        template: &'p str,
+        params: &'p [&'p str],
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Arguments<'a, 'p>, ParseError<'a>> {
+        let arguments = Self::split(content, template, params, diagnostics)?;
+        for arg in arguments.positional.iter().skip(params.len()) {
+            diagnostics.warning(
+                format!("`{template}` takes at most {} arguments", params.len()),
+                arg.raw,
+            );
+        }
+        for (index, arg) in arguments.positional.iter().enumerate() {
+            let Some(name) = params.get(index) else {
+                break;
+            };
+            if !arg.value.is_empty() && arguments.named.iter().any(|x| x.0 == *name) {
+                diagnostics.warning(
+                    format!("argument `{name}` is given both by position and by name"),
+                    arg.raw,
+                );
+            }
+        }
+        Ok(arguments)
+    }
+
+    /// Parses the arguments of a template that takes a single list `param`, given either
+    /// as unnamed arguments or as `param=a,b,c`.
+    pub fn parse_list(
+        content: &'a str,
+        template: &'p str,
+        param: &'p [&'p str; 1],
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Arg<'a>>, ParseError<'a>> {
+        let name = param[0];
+        let mut arguments = Self::split(content, template, param, diagnostics)?;
+        let Some((_, list)) = arguments.named.pop() else {
+            return Ok(arguments.positional);
+        };
+        for arg in &arguments.positional {
+            diagnostics.warning(
+                format!("`{name}` is already given by name, ignoring this argument"),
+                arg.raw,
+            );
+        }
+        split_args(list.raw, ',')
+    }
+
+    /// The argument for the parameter `name`, unless it is missing or empty.
+    pub fn get(&self, name: &str) -> Option<&Arg<'a>> {
+        let named = self.named.iter().find(|x| x.0 == name).map(|x| &x.1);
+        let positional = || {
+            let index = self.params.iter().position(|x| *x == name)?;
+            self.positional.get(index)
+        };
+        named.or_else(positional).filter(|x| !x.value.is_empty())
+    }
+
+    pub fn string(&self, name: &str) -> Option<String> {
+        self.get(name).map(|x| x.value.clone())
+    }
+
+    pub fn required(&self, name: &str) -> Result<&Arg<'a>, ParseError<'a>> {
+        self.get(name).ok_or_else(|| {
+            ParseError::without_location(format!(
+                "`{}` requires the argument `{name}`, e.g. `{{{{{}|{name}=...}}}}`",
+                self.template, self.template
+            ))
+        })
+    }
+
+    /// Parses the argument for `name` into `T`, `expected` describes valid values for the error.
+    pub fn value<T: FromStr>(
+        &self,
+        name: &str,
+        expected: &str,
+    ) -> Result<Option<T>, ParseError<'a>> {
+        let Some(arg) = self.get(name) else {
+            return Ok(None);
+        };
+        let value = arg.value.trim().parse::<T>().map_err(|_| {
+            ParseError::new(
+                format!("`{name}` must be {expected}, found `{}`", arg.value),
+                arg.raw,
+            )
+        })?;
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::types::diagnostic::Diagnostic;
+
+    use super::*;
+
+    const PARAMS: &[&str] = &["src", "size", "alt"];
+
+    fn parse(content: &str) -> (Arguments<'_, 'static>, Vec<Diagnostic>) {
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), content, &mut entries);
+        let arguments = Arguments::parse(content, "Image", PARAMS, &mut diagnostics).unwrap();
+        (arguments, entries)
+    }
+
+    #[test]
+    fn test_split_key() {
+        assert_eq!(split_key("size=200"), Some(("size", "200")));
+        assert_eq!(split_key("\n  alt = Me "), None);
+        assert_eq!(split_key("\n  alt=Me \n"), Some(("alt", "Me")));
+        assert_eq!(split_key("a.jpg"), None);
+        assert_eq!(split_key("=a"), None);
+        assert_eq!(split_key(r"a\=b"), None);
+        assert_eq!(split_key("https://example.com/?a=b"), None);
+    }
+
+    #[test]
+    fn test_positional_and_named() {
+        let (legacy, entries) = parse("sample.jpg,200");
+        assert!(entries.is_empty());
+        let (named, entries) = parse("src=sample.jpg|size=200");
+        assert!(entries.is_empty());
+        let (mixed, entries) = parse("sample.jpg|size=200");
+        assert!(entries.is_empty());
+        for arguments in [legacy, named, mixed] {
+            assert_eq!(arguments.string("src").unwrap(), "sample.jpg");
+            assert_eq!(arguments.value::<u32>("size", "a size").unwrap(), Some(200));
+            assert!(arguments.get("alt").is_none());
+        }
+
+        let (arguments, _) = parse(r#"src=a.jpg|alt="Me, smiling \| happy""#);
+        assert_eq!(arguments.string("alt").unwrap(), "Me, smiling | happy");
+        let (arguments, _) = parse(r"a.jpg,,x\=y");
+        assert_eq!(arguments.string("alt").unwrap(), "x=y");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let (_, entries) = parse("src=a.jpg|sise=200");
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].message.contains("unknown argument `sise`"));
+        assert_eq!(entries[0].span, 10..14);
+
+        let (_, entries) = parse("a.jpg|src=b.jpg");
+        assert!(entries[0].message.contains("both by position and by name"));
+        let (_, entries) = parse("a.jpg,1,alt,extra");
+        assert!(entries[0].message.contains("at most 3 arguments"));
+        let (_, entries) = parse("size=1|size=2");
+        assert!(entries[0].message.contains("more than once"));
+
+        let (arguments, _) = parse("a.jpg,big");
+        let error = arguments.value::<u32>("size", "a positive integer");
+        let error = error.err().unwrap();
+        assert_eq!(error.at.unwrap(), "big");
+        assert_eq!(
+            error.message,
+            "`size` must be a positive integer, found `big`"
+        );
+        let (arguments, _) = parse("size=10");
+        let error = arguments.required("src").err().unwrap();
+        assert!(error.message.contains("requires the argument `src`"));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        for content in ["a,b,c", "pages=a,b,c", "a|b,c"] {
+            let mut entries = Vec::new();
+            let mut diagnostics = Diagnostics::new(Path::new("test.cms"), content, &mut entries);
+            let list =
+                Arguments::parse_list(content, "Navbar", &["pages"], &mut diagnostics).unwrap();
+            let list = list.into_iter().map(|x| x.value).collect::<Vec<_>>();
+            assert_eq!(list, ["a", "b", "c"]);
+            assert!(entries.is_empty());
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::{ops::Range, path::Path};
+
+use crate::types::diagnostic::{Diagnostic, Severity};
+
+/// A fatal problem with a single template. `at` borrows the offending part of the source,
+/// if there is one, so that the span can be recovered when it is reported.
+#[derive(Debug)]
+pub struct ParseError<'a> {
+    pub message: String,
+    pub at: Option<&'a str>,
+}
+
+impl<'a> ParseError<'a> {
+    pub fn new(message: impl Into<String>, at: &'a str) -> Self {
+        ParseError {
+            message: message.into(),
+            at: Some(at),
+        }
+    }
+
+    pub fn without_location(message: impl Into<String>) -> Self {
+        ParseError {
+            message: message.into(),
+            at: None,
+        }
+    }
+
+    pub fn or_at(self, at: &'a str) -> Self {
+        ParseError {
+            at: self.at.or(Some(at)),
+            ..self
+        }
+    }
+}
+
+pub fn require_content<'a>(
+    content: Option<&'a str>,
+    template_name: &str,
+) -> Result<&'a str, ParseError<'a>> {
+    content.ok_or_else(|| {
+        ParseError::without_location(format!(
+            "`{template_name}` requires content, e.g. `{{{{{template_name}|...}}}}`"
+        ))
+    })
+}
+
+/// Collects the diagnostics for one source file.
+pub struct Diagnostics<'a> {
+    file: &'a Path,
+    source: &'a str,
+    entries: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> Diagnostics<'a> {
+    pub fn new(file: &'a Path, source: &'a str, entries: &'a mut Vec<Diagnostic>) -> Self {
+        Diagnostics {
+            file,
+            source,
+            entries,
+        }
+    }
+
+    /// Reports into the same collection, but relative to another source file.
+    pub fn for_file<'b>(&'b mut self, file: &'b Path, source: &'b str) -> Diagnostics<'b> {
+        Diagnostics {
+            file,
+            source,
+            entries: self.entries,
+        }
+    }
+
+    /// Reports relative to `source` in the same file, but into a separate collection.
+    pub fn nested<'b>(&self, source: &'b str, entries: &'b mut Vec<Diagnostic>) -> Diagnostics<'b>
+    where
+        'a: 'b,
+    {
+        Diagnostics {
+            file: self.file,
+            source,
+            entries,
+        }
+    }
+
+    /// The file that diagnostics are reported for.
+    pub fn file(&self) -> &'a Path {
+        self.file
+    }
+
+    fn span_of(&self, at: &str) -> Range<usize> {
+        let base = self.source.as_ptr() as usize;
+        match (at.as_ptr() as usize).checked_sub(base) {
+            Some(start) if start + at.len() <= self.source.len() => start..start + at.len(),
+            _ => 0..0,
+        }
+    }
+
+    pub fn push(&mut self, severity: Severity, message: impl Into<String>, at: &str) {
+        let diagnostic =
+            Diagnostic::new(severity, message, self.file, self.source, self.span_of(at));
+        self.entries.push(diagnostic);
+    }
+
+    pub fn error(&mut self, message: impl Into<String>, at: &str) {
+        self.push(Severity::Error, message, at);
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>, at: &str) {
+        self.push(Severity::Warning, message, at);
+    }
+
+    pub fn report(&mut self, error: ParseError) {
+        self.error(error.message, error.at.unwrap_or_default());
+    }
+
+    /// Number of diagnostics collected so far, to be passed to [`Diagnostics::since`].
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The diagnostics collected after [`Diagnostics::count`] returned `start`.
+    pub fn since(&self, start: usize) -> Vec<Diagnostic> {
+        self.entries[start..].to_vec()
+    }
+
+    /// Adds diagnostics that were collected earlier, e.g. by a cached build.
+    pub fn extend(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.entries.extend(diagnostics);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_span_of() {
+        const SOURCE: &str = "{{Title|hi}}";
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), SOURCE, &mut entries);
+        diagnostics.warning("inside", &SOURCE[2..7]);
+        diagnostics.error("outside", "elsewhere");
+        assert_eq!(entries[0].span, 2..7);
+        assert_eq!(entries[0].severity, Severity::Warning);
+        assert_eq!(entries[1].span, 0..0);
+    }
+
+    #[test]
+    fn test_for_file() {
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let other = "{{Date|x}}";
+        diagnostics
+            .for_file(Path::new("post.cms"), other)
+            .report(ParseError::new("bad date", &other[7..8]));
+        assert_eq!(entries[0].file, Path::new("post.cms"));
+        assert_eq!(entries[0].span, 7..8);
+    }
+
+    #[test]
+    fn test_since() {
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        diagnostics.warning("first", "");
+        let start = diagnostics.count();
+        diagnostics.warning("second", "");
+        let since = diagnostics.since(start);
+        assert_eq!(since.len(), 1);
+        assert_eq!(since[0].message, "second");
+        diagnostics.extend(since);
+        assert_eq!(entries.len(), 3);
+    }
+}
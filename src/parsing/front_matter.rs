@@ -0,0 +1,449 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::types::{cms_blog::slugify, cms_image::CMSImage, generation_dirs::GenerationDirs};
+
+use super::{
+    diagnostics::{Diagnostics, ParseError},
+    parse_template_elements::{load_image, parse_date_time},
+};
+
+/// The language of a front matter, given by the lines around it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrontMatterFormat {
+    /// Between two `+++` lines
+    Toml,
+    /// Between two `---` lines
+    Yaml,
+}
+
+impl FrontMatterFormat {
+    fn delimiter(self) -> &'static str {
+        match self {
+            FrontMatterFormat::Toml => "+++",
+            FrontMatterFormat::Yaml => "---",
+        }
+    }
+}
+
+/// A value of the front matter as it is written, which knows where it was written.
+trait FieldValue {
+    /// The value if it is a string, otherwise the name of its type.
+    fn as_text(&self) -> Result<&str, &'static str>;
+    /// The text of a date, which may also be a TOML date.
+    fn as_date(&self) -> Result<String, &'static str>;
+    fn at<'a>(&self, source: &'a str, key: &str) -> &'a str;
+}
+
+impl FieldValue for Spanned<toml::Value> {
+    fn as_text(&self) -> Result<&str, &'static str> {
+        match self.get_ref() {
+            toml::Value::String(x) => Ok(x),
+            other => Err(other.type_str()),
+        }
+    }
+
+    fn as_date(&self) -> Result<String, &'static str> {
+        match self.get_ref() {
+            toml::Value::Datetime(x) => Ok(x.to_string()),
+            _ => self.as_text().map(String::from),
+        }
+    }
+
+    fn at<'a>(&self, source: &'a str, _key: &str) -> &'a str {
+        source.get(self.span()).unwrap_or(source)
+    }
+}
+
+impl FieldValue for serde_yaml::Value {
+    fn as_text(&self) -> Result<&str, &'static str> {
+        match self {
+            serde_yaml::Value::String(x) => Ok(x),
+            serde_yaml::Value::Null => Err("nothing"),
+            serde_yaml::Value::Bool(_) => Err("boolean"),
+            serde_yaml::Value::Number(_) => Err("number"),
+            serde_yaml::Value::Sequence(_) => Err("list"),
+            serde_yaml::Value::Mapping(_) => Err("mapping"),
+            serde_yaml::Value::Tagged(_) => Err("tagged value"),
+        }
+    }
+
+    fn as_date(&self) -> Result<String, &'static str> {
+        self.as_text().map(String::from)
+    }
+
+    /// YAML values do not know their location, so this is the line of their key.
+    fn at<'a>(&self, source: &'a str, key: &str) -> &'a str {
+        source
+            .lines()
+            .find(|line| {
+                line.trim_start()
+                    .strip_prefix(key)
+                    .is_some_and(|x| x.trim_start().starts_with(':'))
+            })
+            .map(str::trim)
+            .unwrap_or(source)
+    }
+}
+
+/// The front matter as it is written.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawFrontMatter<V> {
+    title: Option<V>,
+    date: Option<V>,
+    updated: Option<V>,
+    tags: Vec<V>,
+    author: Option<V>,
+    slug: Option<V>,
+    summary: Option<V>,
+    draft: bool,
+    cover: Option<V>,
+}
+
+impl<V> Default for RawFrontMatter<V> {
+    fn default() -> Self {
+        RawFrontMatter {
+            title: None,
+            date: None,
+            updated: None,
+            tags: Vec::new(),
+            author: None,
+            slug: None,
+            summary: None,
+            draft: false,
+            cover: None,
+        }
+    }
+}
+
+/// The metadata of a blog post given in TOML between two `+++` lines, or in YAML between
+/// two `---` lines, at the top of the file.
+#[derive(Default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub slug: Option<String>,
+    pub summary: Option<String>,
+    pub draft: bool,
+    pub cover: Option<CMSImage>,
+}
+
+/// The front matter of a blog post together with its format, if the post starts with one,
+/// and the rest of the post.
+pub type SplitPost<'a> = (Option<(FrontMatterFormat, &'a str)>, &'a str);
+
+/// Splits a blog post into its front matter and the rest.
+pub fn split_front_matter(contents: &str) -> Result<SplitPost<'_>, ParseError<'_>> {
+    let start = contents.trim_start();
+    let Some(format) = [FrontMatterFormat::Toml, FrontMatterFormat::Yaml]
+        .into_iter()
+        .find(|x| start.starts_with(x.delimiter()))
+    else {
+        return Ok((None, contents));
+    };
+    let delimiter = format.delimiter();
+    let rest = &start[delimiter.len()..];
+    let Some(rest) = rest
+        .strip_prefix('\n')
+        .or_else(|| rest.strip_prefix("\r\n"))
+    else {
+        return Ok((None, contents));
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            let body = &rest[offset + line.len()..];
+            return Ok((Some((format, &rest[..offset])), body));
+        }
+        offset += line.len();
+    }
+    Err(ParseError::new(
+        format!("front matter is not closed with a `{delimiter}` line"),
+        &start[..delimiter.len()],
+    ))
+}
+
+fn text<'a>(source: &'a str, key: &str, value: &impl FieldValue) -> Result<String, ParseError<'a>> {
+    let text = value.as_text().map_err(|found| {
+        ParseError::new(
+            format!("`{key}` must be a string, found {found}"),
+            value.at(source, key),
+        )
+    })?;
+    Ok(text.to_string())
+}
+
+/// Reads a date in any format that `{{Date}}` accepts.
+fn date<'a>(
+    source: &'a str,
+    key: &str,
+    value: &impl FieldValue,
+    generation_dirs: &impl GenerationDirs,
+) -> Result<DateTime<Utc>, ParseError<'a>> {
+    let timezone = generation_dirs.get_config().timezone;
+    value
+        .as_date()
+        .map_err(|found| format!("`{key}` must be a date, found {found}"))
+        .and_then(|x| parse_date_time(&x, timezone))
+        .map_err(|e| ParseError::new(e, value.at(source, key)))
+}
+
+fn optional<'a, V, T>(
+    value: Option<&V>,
+    parse: impl FnOnce(&V) -> Result<T, ParseError<'a>>,
+) -> Result<Option<T>, ParseError<'a>> {
+    value.map(parse).transpose()
+}
+
+fn resolve_front_matter<'a, V: FieldValue>(
+    raw: RawFrontMatter<V>,
+    source: &'a str,
+    generation_dirs: &impl GenerationDirs,
+    diagnostics: &mut Diagnostics,
+) -> Result<FrontMatter, ParseError<'a>> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in &raw.tags {
+        let name = text(source, "tags", tag)?;
+        let name = name.trim();
+        if slugify(name).is_empty() {
+            diagnostics.warning(
+                format!("tag `{name}` needs at least one letter or digit"),
+                tag.at(source, "tags"),
+            );
+        } else if tags.iter().any(|x| slugify(x) == slugify(name)) {
+            diagnostics.warning(
+                format!("tag `{name}` is given more than once"),
+                tag.at(source, "tags"),
+            );
+        } else {
+            tags.push(name.to_string());
+        }
+    }
+    let slug = optional(raw.slug.as_ref(), |slug| {
+        let slugified = slugify(&text(source, "slug", slug)?);
+        if slugified.is_empty() {
+            return Err(ParseError::new(
+                "`slug` needs at least one letter or digit",
+                slug.at(source, "slug"),
+            ));
+        }
+        Ok(slugified)
+    })?;
+    let cover = optional(raw.cover.as_ref(), |cover| {
+        let (url, copy_asset) = load_image(&text(source, "cover", cover)?, None, generation_dirs)
+            .map_err(|e| ParseError::new(e, cover.at(source, "cover")))?;
+        Ok(CMSImage {
+            url,
+            copy_asset,
+            size: None,
+            alt: String::new(),
+            caption: None,
+            link: None,
+            align: None,
+        })
+    })?;
+    Ok(FrontMatter {
+        title: optional(raw.title.as_ref(), |x| text(source, "title", x))?,
+        date: optional(raw.date.as_ref(), |x| {
+            date(source, "date", x, generation_dirs)
+        })?,
+        updated: optional(raw.updated.as_ref(), |x| {
+            date(source, "updated", x, generation_dirs)
+        })?,
+        tags,
+        author: optional(raw.author.as_ref(), |x| text(source, "author", x))?,
+        slug,
+        summary: optional(raw.summary.as_ref(), |x| text(source, "summary", x))?,
+        draft: raw.draft,
+        cover,
+    })
+}
+
+/// Parses a front matter. Invalid fields are errors, since the post would otherwise be
+/// published differently than intended.
+pub fn parse_front_matter<'a>(
+    source: &'a str,
+    format: FrontMatterFormat,
+    generation_dirs: &impl GenerationDirs,
+    diagnostics: &mut Diagnostics,
+) -> Result<FrontMatter, ParseError<'a>> {
+    match format {
+        FrontMatterFormat::Toml => {
+            let raw: RawFrontMatter<Spanned<toml::Value>> =
+                toml::from_str(source).map_err(|e| {
+                    let at = e.span().and_then(|x| source.get(x)).unwrap_or(source);
+                    ParseError::new(format!("invalid front matter: {}", e.message()), at)
+                })?;
+            resolve_front_matter(raw, source, generation_dirs, diagnostics)
+        }
+        FrontMatterFormat::Yaml if source.trim().is_empty() => Ok(FrontMatter::default()),
+        FrontMatterFormat::Yaml => {
+            let raw: RawFrontMatter<serde_yaml::Value> =
+                serde_yaml::from_str(source).map_err(|e| {
+                    let at = e
+                        .location()
+                        .and_then(|x| source.get(x.index()..))
+                        .and_then(|x| x.lines().next())
+                        .unwrap_or(source);
+                    ParseError::new(format!("invalid front matter: {e}"), at)
+                })?;
+            resolve_front_matter(raw, source, generation_dirs, diagnostics)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use chrono::TimeZone;
+
+    use crate::types::{diagnostic::Diagnostic, generation_dirs::TempGenerationDirs};
+
+    use super::*;
+
+    fn parse(
+        source: &str,
+        format: FrontMatterFormat,
+    ) -> (Result<FrontMatter, String>, Vec<Diagnostic>) {
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("post.cms"), source, &mut entries);
+        let front_matter = parse_front_matter(source, format, &generation_dirs, &mut diagnostics)
+            .map_err(|e| {
+                let at = e.at.unwrap_or_default().to_string();
+                format!("{} at `{at}`", e.message)
+            });
+        (front_matter, entries)
+    }
+
+    #[test]
+    fn test_split_front_matter() {
+        let (front_matter, body) =
+            split_front_matter("\n+++\ntitle = \"Hi\"\n+++\n{{Paragraph|x}}").unwrap();
+        assert_eq!(
+            front_matter,
+            Some((FrontMatterFormat::Toml, "title = \"Hi\"\n"))
+        );
+        assert_eq!(body, "{{Paragraph|x}}");
+        let (front_matter, body) = split_front_matter("---\r\ntitle: Hi\r\n---\r\n# Hi").unwrap();
+        assert_eq!(
+            front_matter,
+            Some((FrontMatterFormat::Yaml, "title: Hi\r\n"))
+        );
+        assert_eq!(body, "# Hi");
+        for contents in ["{{Title|x}}\n+++\n+++", "+++ not front matter", "----\n"] {
+            assert_eq!(split_front_matter(contents).unwrap(), (None, contents));
+        }
+        let error = split_front_matter("+++\ntitle = \"Hi\"\n").unwrap_err();
+        assert!(error.message.contains("not closed with a `+++` line"));
+        let error = split_front_matter("---\ntitle: Hi\n+++\n").unwrap_err();
+        assert!(error.message.contains("not closed with a `---` line"));
+    }
+
+    #[test]
+    fn test_parse_front_matter() {
+        let toml = r#"
+            title = "My first toy"
+            date = 2023-12-31T18:00:00+01:00
+            updated = "2024-01-02"
+            tags = ["Rust", "code", "rust", "!!"]
+            author = "Naresh"
+            slug = "First Toy"
+            summary = "About a toy"
+            draft = true
+            "#;
+        let yaml = r#"
+            title: My first toy
+            date: 2023-12-31T18:00:00+01:00
+            updated: "2024-01-02"
+            tags: [Rust, code, rust, "!!"]
+            author: Naresh
+            slug: First Toy
+            summary: About a toy
+            draft: true
+            "#;
+        for (source, format) in [
+            (toml, FrontMatterFormat::Toml),
+            (yaml, FrontMatterFormat::Yaml),
+        ] {
+            let (front_matter, entries) = parse(source, format);
+            let front_matter = front_matter.unwrap();
+            assert_eq!(front_matter.title.unwrap(), "My first toy");
+            assert_eq!(
+                front_matter.date,
+                Some(Utc.with_ymd_and_hms(2023, 12, 31, 17, 0, 0).unwrap())
+            );
+            assert_eq!(
+                front_matter.updated,
+                Some(Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap())
+            );
+            assert_eq!(front_matter.tags, ["Rust", "code"]);
+            assert_eq!(front_matter.author.unwrap(), "Naresh");
+            assert_eq!(front_matter.slug.unwrap(), "first-toy");
+            assert_eq!(front_matter.summary.unwrap(), "About a toy");
+            assert!(front_matter.draft);
+            assert_eq!(entries.len(), 2);
+            assert!(entries[0]
+                .message
+                .contains("`rust` is given more than once"));
+            assert!(entries[1]
+                .message
+                .contains("`!!` needs at least one letter"));
+        }
+
+        let (front_matter, _) = parse("date = 2023-12-31", FrontMatterFormat::Toml);
+        assert!(front_matter.unwrap().date.is_some());
+        let (front_matter, _) = parse("cover = \"sample.jpg\"", FrontMatterFormat::Toml);
+        assert!(front_matter.unwrap().cover.is_some());
+        let (front_matter, _) = parse("\n", FrontMatterFormat::Yaml);
+        assert!(front_matter.unwrap().title.is_none());
+    }
+
+    #[test]
+    fn test_front_matter_errors() {
+        for (source, message) in [
+            ("titel = \"x\"", "unknown field `titel`"),
+            ("date = \"31.12.2023\"", "+01:00` at `\"31.12.2023\"`"),
+            ("date = 12", "`date` must be a date, found integer at `12`"),
+            ("updated = 12:00:00", "invalid date `12:00:00`"),
+            (
+                "draft = \"yes\"",
+                "invalid type: string \"yes\", expected a boolean",
+            ),
+            ("slug = \"!!\"", "`slug` needs at least one letter or digit"),
+            ("title = 1", "`title` must be a string, found integer"),
+            (
+                "cover = \"missing.jpg\"",
+                "could not read image `missing.jpg`",
+            ),
+            ("title = ", "invalid front matter"),
+        ] {
+            let (front_matter, _) = parse(source, FrontMatterFormat::Toml);
+            let error = front_matter.err().unwrap();
+            assert!(error.contains(message), "{error}");
+        }
+        for (source, message) in [
+            ("titel: x", "unknown field `titel`"),
+            ("title: x\ndate: 31.12.2023", "at `date: 31.12.2023`"),
+            (
+                "  date: 2023",
+                "`date` must be a date, found number at `date: 2023`",
+            ),
+            (
+                "slug: [a]",
+                "`slug` must be a string, found list at `slug: [a]`",
+            ),
+            ("title: [", "invalid front matter"),
+        ] {
+            let (front_matter, _) = parse(source, FrontMatterFormat::Yaml);
+            let error = front_matter.err().unwrap();
+            assert!(error.contains(message), "{error}");
+        }
+    }
+}
@@ -1,6 +1,12 @@
-mod get_tags;
+mod arguments;
+mod diagnostics;
+mod front_matter;
 mod parse_blog;
+mod parse_include;
 mod parse_template_elements;
+mod scope;
+mod tokenizer;
+mod user_templates;
 
 use std::collections::HashMap;
 
@@ -9,70 +15,178 @@ use crate::types::{
     template_type::TemplateType,
 };
 
-use self::{get_tags::get_tags, parse_blog::parse_blog, parse_template_elements::*};
+use self::{
+    diagnostics::{require_content, Diagnostics, ParseError},
+    parse_blog::parse_blog,
+    parse_include::parse_include,
+    parse_template_elements::*,
+    scope::Scope,
+    tokenizer::get_tags,
+};
 
 enum ParseElements {
     Template(TemplateType),
     Page(CMSPage),
+    /// The result of expanding a user-defined template or including a file
+    Expanded(Vec<TemplateType>, HashMap<String, CMSPage>),
 }
 
-fn parse_page(content: Option<&str>, generation_dirs: &impl GenerationDirs) -> Option<CMSPage> {
-    let content = content?;
-    let (templates, _pages) = parse_templates(content, generation_dirs);
-    Some(CMSPage { templates })
+fn parse_page<'a>(
+    content: Option<&'a str>,
+    generation_dirs: &impl GenerationDirs,
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
+) -> Result<CMSPage, ParseError<'a>> {
+    let content = require_content(content, "Page")?;
+    let (templates, _pages) = parse_templates(content, generation_dirs, scope, diagnostics);
+    Ok(CMSPage { templates })
+}
+
+/// Splits `Name|content` into the name of the template and its content, if there is any.
+fn split_template(template_content: &str) -> (&str, Option<&str>) {
+    match template_content.split_once('|') {
+        Some((template_name, template_content)) => (template_name, Some(template_content)),
+        None => (template_content, None),
+    }
 }
 
-fn parse_template(
-    template_content: &str,
+/// Expands a user-defined template and parses the result. Problems inside the expansion
+/// are reported at the place where the template is used.
+fn parse_user_template<'a>(
+    template_name: &str,
+    template_content: &'a str,
     generation_dirs: &impl GenerationDirs,
-) -> Option<ParseElements> {
-    let template_separator = template_content.match_indices('|').next().map(|x| x.0);
-    let (template_name, template_content) = match template_separator {
-        Some(template_separator) => (
-            template_content.get(0..template_separator)?,
-            template_content.get(template_separator + 1..),
-        ),
-        _ => (template_content, None),
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
+) -> Result<ParseElements, ParseError<'a>> {
+    let (name, content) = split_template(template_content);
+    let (expanded, user_templates) =
+        scope
+            .user_templates
+            .expand(template_name, content, diagnostics)?;
+    let scope = Scope {
+        user_templates,
+        ..scope.clone()
     };
+    let mut entries = Vec::new();
+    let (templates, pages) = parse_templates(
+        &expanded,
+        generation_dirs,
+        &scope,
+        &mut diagnostics.nested(&expanded, &mut entries),
+    );
+    for entry in entries {
+        diagnostics.push(
+            entry.severity,
+            format!("in `{template_name}`: {}", entry.message),
+            name,
+        );
+    }
+    Ok(ParseElements::Expanded(templates, pages))
+}
+
+fn parse_template<'a>(
+    template_content: &'a str,
+    generation_dirs: &impl GenerationDirs,
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
+) -> Result<ParseElements, ParseError<'a>> {
+    let (template_name, content) = split_template(template_content);
+    if scope.user_templates.contains(template_name.trim()) {
+        return parse_user_template(
+            template_name.trim(),
+            template_content,
+            generation_dirs,
+            scope,
+            diagnostics,
+        );
+    }
+    let template_content = content;
     match template_name {
-        "Navbar" => parse_navbar(template_content).map(ParseElements::Template),
+        "Navbar" => parse_navbar(template_content, diagnostics).map(ParseElements::Template),
         "Title" => parse_title(template_content).map(ParseElements::Template),
         "Paragraph" => parse_paragraph(template_content).map(ParseElements::Template),
-        "Links" => parse_links(template_content).map(ParseElements::Template),
-        "NKR-CMS-INFO" => parse_nkr_cms_info().map(ParseElements::Template),
-        "Image" => parse_image(template_content, generation_dirs).map(ParseElements::Template),
+        "Markdown" => parse_markdown(template_content).map(ParseElements::Template),
+        "Html" => parse_html(template_content).map(ParseElements::Template),
+        "Links" => parse_links(template_content, diagnostics).map(ParseElements::Template),
+        "NKR-CMS-INFO" => parse_nkr_cms_info(generation_dirs).map(ParseElements::Template),
+        "Image" => {
+            parse_image(template_content, generation_dirs, diagnostics).map(ParseElements::Template)
+        }
         "Name" => parse_name(template_content).map(ParseElements::Template),
-        "Page" => parse_page(template_content, generation_dirs).map(ParseElements::Page),
-        "Date" => parse_date(template_content).map(ParseElements::Template),
-        "Blog" => parse_blog(template_content, generation_dirs).map(ParseElements::Template),
-        "Code" => parse_code(template_content).map(ParseElements::Template),
-        _ => None,
+        "Page" => parse_page(template_content, generation_dirs, scope, diagnostics)
+            .map(ParseElements::Page),
+        "Date" => {
+            parse_date(template_content, generation_dirs, diagnostics).map(ParseElements::Template)
+        }
+        "Blog" => parse_blog(template_content, generation_dirs, scope, diagnostics)
+            .map(ParseElements::Template),
+        "Include" => parse_include(template_content, generation_dirs, scope, diagnostics),
+        "Code" => parse_code(template_content, diagnostics).map(ParseElements::Template),
+        "Tags" => parse_tags(template_content, diagnostics).map(ParseElements::Template),
+        "Draft" => parse_draft(template_content, diagnostics).map(ParseElements::Template),
+        _ => Err(ParseError::new(
+            format!("unknown template `{}`", template_name.trim()),
+            template_name,
+        )),
     }
 }
 
+/// Parses all templates in `content`. Templates defined with `{{Define|...}}` can be used
+/// anywhere in `content`, including nested pages, blog posts and included files.
 fn parse_templates(
     content: &str,
     generation_dirs: &impl GenerationDirs,
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
 ) -> (Vec<TemplateType>, HashMap<String, CMSPage>) {
     let mut result: Vec<TemplateType> = Vec::new();
     let mut pages: HashMap<String, CMSPage> = HashMap::new();
 
-    let tags = get_tags(content);
-    if let Some(tags) = tags {
-        for template_content in tags {
-            let template = parse_template(template_content, generation_dirs);
-            if let Some(ParseElements::Template(template)) = template {
-                result.push(template);
-            } else if let Some(ParseElements::Page(cms_page)) = template {
+    let tags = match get_tags(content) {
+        Ok(tags) => tags,
+        Err(e) => {
+            diagnostics.report(e);
+            return (result, pages);
+        }
+    };
+    let mut scope = scope.clone();
+    let (definitions, tags): (Vec<_>, Vec<_>) = tags
+        .into_iter()
+        .partition(|x| split_template(x).0 == "Define");
+    for definition in definitions {
+        if let Err(e) = scope
+            .user_templates
+            .define(split_template(definition).1, diagnostics)
+        {
+            diagnostics.report(e.or_at(definition));
+        }
+    }
+    for template_content in tags {
+        let template = parse_template(template_content, generation_dirs, &scope, diagnostics);
+        match template {
+            Ok(ParseElements::Template(template)) => result.push(template),
+            Ok(ParseElements::Expanded(templates, expanded_pages)) => {
+                result.extend(templates);
+                pages.extend(expanded_pages);
+            }
+            Ok(ParseElements::Page(cms_page)) => {
                 let name = cms_page
                     .templates
                     .iter()
                     .filter_map(|x| x.get_name())
                     .next();
-                if let Some(name) = name {
-                    pages.insert(name.to_string(), cms_page);
+                match name {
+                    Some(name) => {
+                        pages.insert(name.to_string(), cms_page);
+                    }
+                    None => diagnostics.error(
+                        "`Page` has no `{{Name|...}}` and will not be generated",
+                        template_content,
+                    ),
                 }
             }
+            Err(e) => diagnostics.report(e.or_at(template_content)),
         }
     }
     (result, pages)
@@ -81,25 +195,34 @@ fn parse_templates(
 pub fn parse_file(generation_dirs: &impl GenerationDirs) -> Result<CMSSite, std::io::Error> {
     let file_path = &generation_dirs.in_source("index.cms");
     let contents = std::fs::read_to_string(file_path)?;
-    let (templates, pages) = parse_templates(&contents, generation_dirs);
+    let mut entries = Vec::new();
+    let mut diagnostics = Diagnostics::new(file_path, &contents, &mut entries);
+    let (templates, pages) = parse_templates(
+        &contents,
+        generation_dirs,
+        &Scope::default(),
+        &mut diagnostics,
+    );
     Ok(CMSSite {
-        original_content: contents,
         templates,
         pages,
+        diagnostics: entries,
     })
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::types::generation_dirs::TempGenerationDirs;
+    use std::path::Path;
+
+    use crate::types::{generation_dirs::TempGenerationDirs, template_type::TemplateTypeVector};
 
     use super::*;
     #[test]
     fn test_parse_templates() {
         const CONTENT: &str = r#"
         {{Title|hi}}
-        {{Image|sample.jpg}}
+        {{Image|sample.jpg,,A sample image}}
         {{Navbar|bla,bla}}
         {{Links|Github:bla}}
         {{NKR-CMS-INFO}}
@@ -110,11 +233,93 @@ mod test {
         }}
         "#;
         let generation_dirs = TempGenerationDirs::default();
-        let (templates, pages) = parse_templates(CONTENT, &generation_dirs);
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), CONTENT, &mut entries);
+        let (templates, pages) = parse_templates(
+            CONTENT,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        );
         assert_eq!(templates.len(), 5);
         assert_eq!(pages.len(), 1);
-        let (templates, pages) = parse_templates("{{broken_content", &generation_dirs);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "unknown template `Nonsense`");
+        assert_eq!(entries[0].line, 7);
+        assert_eq!(entries[0].column, 11);
+
+        const BROKEN: &str = "{{broken_content";
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), BROKEN, &mut entries);
+        let (templates, pages) = parse_templates(
+            BROKEN,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        );
         assert_eq!(templates.len(), 0);
         assert_eq!(pages.len(), 0);
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_template_errors() {
+        const CONTENT: &str = "{{Page|{{Paragraph|hi}}}}\n{{Title}}";
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), CONTENT, &mut entries);
+        parse_templates(
+            CONTENT,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        );
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].message.contains("`Page` has no"));
+        assert!(entries[1].message.contains("`Title` requires content"));
+        assert_eq!(entries[1].span, 28..33);
+    }
+
+    #[test]
+    fn test_user_templates() {
+        const CONTENT: &str = r#"
+        {{Page|
+        {{Name|home}}
+        {{Card|title=Hello|text=World}}
+        {{Loop}}
+        }}
+        {{Define|Card|params=title,text|{{Title|{{$title}}}}{{Paragraph|{{$text}}}}{{Date|{{$title}}}}}}
+        {{Define|Loop|{{Loop}}}}
+        "#;
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), CONTENT, &mut entries);
+        let (_, pages) = parse_templates(
+            CONTENT,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        );
+        let templates = &pages["home"].templates;
+        assert_eq!(templates.get_title().unwrap(), "Hello");
+        assert_eq!(
+            templates.iter().find_map(|x| x.get_paragraph()).unwrap(),
+            "World"
+        );
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0]
+            .message
+            .starts_with("in `Card`: invalid date `Hello"));
+        assert_eq!(entries[0].line, 4);
+        assert_eq!(
+            entries[1].message,
+            "in `Loop`: template `Loop` uses itself (Loop -> Loop)"
+        );
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let cms_site = parse_file(&TempGenerationDirs::default()).unwrap();
+        assert!(cms_site.diagnostics.is_empty());
     }
 }
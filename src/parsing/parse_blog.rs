@@ -1,14 +1,38 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use pulldown_cmark::{Event, Parser, Tag};
 
 use crate::{
-    parsing::parse_templates,
+    build_cache::hash_bytes,
+    parsing::{
+        arguments::Arguments,
+        diagnostics::{require_content, Diagnostics, ParseError},
+        front_matter::{parse_front_matter, split_front_matter, FrontMatter},
+        parse_include::resolve,
+        parse_template_elements::load_image,
+        parse_templates,
+        scope::Scope,
+    },
     types::{
-        cms_blog::{BlogPost, CMSBlog},
+        cms_blog::{slugify, BlogPost, CMSBlog},
+        cms_image::CMSImage,
         generation_dirs::GenerationDirs,
         template_type::{TemplateType, TemplateTypeVector},
     },
 };
 
+/// Extensions of the files in a blog directory that are blog posts. Other files, like the
+/// images of the posts, are left alone.
+const POST_EXTENSIONS: &[&str] = &["cms", "md"];
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|x| x == "md")
+}
+
 fn get_files_in_dir(path: &Path) -> Option<Vec<PathBuf>> {
     Some(
         std::fs::read_dir(path)
@@ -16,7 +40,8 @@ fn get_files_in_dir(path: &Path) -> Option<Vec<PathBuf>> {
             .filter_map(|x| {
                 let x = x.ok()?;
                 let file_type = x.file_type().ok()?;
-                if file_type.is_file() {
+                let extension = x.path().extension()?.to_str()?.to_string();
+                if file_type.is_file() && POST_EXTENSIONS.contains(&extension.as_str()) {
                     return Some(x.path());
                 }
                 None
@@ -25,32 +50,258 @@ fn get_files_in_dir(path: &Path) -> Option<Vec<PathBuf>> {
     )
 }
 
-fn parse_blog_file(path: &Path, generation_dirs: &impl GenerationDirs) -> Option<BlogPost> {
-    let contents = std::fs::read_to_string(path).ok()?;
-    let (templates, _pages) = parse_templates(&contents, generation_dirs);
-    let post_date = templates.get_date()?;
+/// Posts with the same slug would be written to the same path, so only the first one of them
+/// is kept, in the order of their files.
+fn unique_slugs(
+    mut posts: Vec<(&PathBuf, BlogPost)>,
+    diagnostics: &mut Diagnostics,
+) -> Vec<BlogPost> {
+    posts.sort_by(|a, b| a.0.cmp(b.0));
+    let mut unique: Vec<(&PathBuf, BlogPost)> = Vec::new();
+    for (path, post) in posts {
+        match unique.iter().find(|x| x.1.slug == post.slug) {
+            Some((first, _)) => diagnostics.for_file(path, "").warning(
+                format!(
+                    "blog post has the same slug `{}` as `{}` and will not be published",
+                    post.slug,
+                    first.display()
+                ),
+                "",
+            ),
+            None => unique.push((path, post)),
+        }
+    }
+    unique.into_iter().map(|x| x.1).collect()
+}
+
+/// Whether `url` points to a file next to the post, rather than to another website or a data URL.
+fn is_local(url: &str) -> bool {
+    !url.is_empty() && !url.contains(':') && !url.starts_with(['/', '#'])
+}
+
+/// A Markdown blog post becomes a single `Markdown` template. Its local images are resolved
+/// relative to the post and, like those of `{{Image}}`, inlined or copied.
+fn parse_markdown_post(
+    body: &str,
+    path: &Path,
+    generation_dirs: &impl GenerationDirs,
+    diagnostics: &mut Diagnostics,
+) -> TemplateType {
+    let mut images = BTreeMap::new();
+    for (event, range) in Parser::new(body).into_offset_iter() {
+        let Event::Start(Tag::Image { dest_url, .. }) = event else {
+            continue;
+        };
+        if !is_local(&dest_url) || images.contains_key(dest_url.as_ref()) {
+            continue;
+        }
+        let Some(resolved) = resolve(path, &dest_url, &generation_dirs.get_source_dir()) else {
+            diagnostics.warning(
+                format!("image `{dest_url}` is outside of the source directory"),
+                &body[range],
+            );
+            continue;
+        };
+        let resolved = resolved.to_string_lossy();
+        match load_image(&resolved, None, generation_dirs) {
+            Ok((url, copy_asset)) => {
+                let image = CMSImage {
+                    url,
+                    copy_asset,
+                    size: None,
+                    alt: String::new(),
+                    caption: None,
+                    link: None,
+                    align: None,
+                };
+                images.insert(dest_url.to_string(), image);
+            }
+            Err(e) => diagnostics.warning(e, &body[range]),
+        }
+    }
+    TemplateType::Markdown {
+        content: body.to_string(),
+        images,
+    }
+}
+
+/// Turns the file name of a blog post into the last segment of its URL. Returns `None` if
+/// the name has no letters or digits, which would give an empty segment.
+fn get_slug(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_string_lossy();
+    Some(slugify(&stem)).filter(|x| !x.is_empty())
+}
+
+/// Parses a blog post, or reuses the post from a previous build if none of its files changed.
+fn parse_blog_file(
+    path: &Path,
+    generation_dirs: &impl GenerationDirs,
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
+) -> Option<BlogPost> {
+    let cache = generation_dirs.get_cache();
+    let context = [
+        scope.user_templates.fingerprint(),
+        generation_dirs.get_config().fingerprint(),
+    ];
+    let context = hash_bytes(&context.map(u64::to_le_bytes).concat());
+    if let Some(cached) = cache.get_post(path, context) {
+        log::trace!("Using cached blog post {}", path.display());
+        diagnostics.extend(cached.diagnostics);
+        return cached.post;
+    }
+    let start = diagnostics.count();
+    let (post, dependencies) =
+        cache.record(|| parse_blog_file_uncached(path, generation_dirs, scope, diagnostics));
+    cache.insert_post(
+        path,
+        context,
+        dependencies,
+        post.clone(),
+        diagnostics.since(start),
+    );
+    post
+}
+
+fn parse_blog_file_uncached(
+    path: &Path,
+    generation_dirs: &impl GenerationDirs,
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
+) -> Option<BlogPost> {
+    let contents = match generation_dirs.get_cache().read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            diagnostics
+                .for_file(path, "")
+                .error(format!("could not read blog post: {e}"), "");
+            return None;
+        }
+    };
+    let mut diagnostics = diagnostics.for_file(path, &contents);
+    let first_line = contents.lines().next().unwrap_or_default();
+    let front_matter = split_front_matter(&contents).and_then(|(front_matter, body)| {
+        let front_matter = match front_matter {
+            Some((format, x)) => parse_front_matter(x, format, generation_dirs, &mut diagnostics)?,
+            None => FrontMatter::default(),
+        };
+        Ok((front_matter, body))
+    });
+    let (front_matter, body) = match front_matter {
+        Ok(x) => x,
+        Err(e) => {
+            diagnostics.report(e);
+            return None;
+        }
+    };
+    let templates = match is_markdown(path) {
+        true => Vec::from([parse_markdown_post(
+            body,
+            path,
+            generation_dirs,
+            &mut diagnostics,
+        )]),
+        false => parse_templates(body, generation_dirs, scope, &mut diagnostics).0,
+    };
+    let Some(title) = front_matter
+        .title
+        .or_else(|| templates.get_title().cloned())
+    else {
+        diagnostics.warning(
+            "blog post has no `title` or `{{Title|...}}` and will not be published",
+            first_line,
+        );
+        return None;
+    };
+    let Some(post_date) = front_matter.date.or_else(|| templates.get_date().copied()) else {
+        diagnostics.warning(
+            "blog post has no `date` or `{{Date|...}}` and will not be published",
+            first_line,
+        );
+        return None;
+    };
+    let Some(slug) = front_matter.slug.or_else(|| get_slug(path)) else {
+        diagnostics.warning(
+            "the file name of the blog post has no letters or digits to use in its URL, \
+            set `slug` in the front matter; it will not be published",
+            first_line,
+        );
+        return None;
+    };
+    let tags = match front_matter.tags.is_empty() {
+        true => templates.get_tags().cloned().unwrap_or_default(),
+        false => front_matter.tags,
+    };
 
     Some(BlogPost {
-        post_date: *post_date,
+        slug,
+        title,
+        post_date,
+        updated: front_matter.updated,
+        tags,
+        author: front_matter.author,
+        summary: front_matter.summary,
+        cover: front_matter.cover,
+        draft: front_matter.draft || templates.is_draft(),
         templates,
     })
 }
 
-pub fn parse_blog(
-    content: Option<&str>,
-    generation_dirs: &impl GenerationDirs,
-) -> Option<TemplateType> {
-    let blog_dir = generation_dirs.in_source(content?);
-    if !blog_dir.exists() {
-        return None;
+/// Leaves out drafts and posts that are dated after `now`, unless `drafts` is set.
+/// Returns the published posts and the date of the earliest scheduled post.
+fn publish(
+    posts: Vec<BlogPost>,
+    now: DateTime<Utc>,
+    drafts: bool,
+) -> (Vec<BlogPost>, Option<DateTime<Utc>>) {
+    if drafts {
+        return (posts, None);
     }
+    let mut next_publication: Option<DateTime<Utc>> = None;
+    let mut published = Vec::new();
+    for post in posts {
+        if post.draft {
+            log::debug!("Not publishing draft `{}`", post.slug);
+        } else if post.post_date > now {
+            log::debug!("`{}` is scheduled for {}", post.slug, post.post_date);
+            next_publication =
+                Some(next_publication.map_or(post.post_date, |x| x.min(post.post_date)));
+        } else {
+            published.push(post);
+        }
+    }
+    (published, next_publication)
+}
 
-    let blog_files = get_files_in_dir(&blog_dir)?;
+pub fn parse_blog<'a>(
+    content: Option<&'a str>,
+    generation_dirs: &impl GenerationDirs,
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    let content = require_content(content, "Blog")?;
+    let args = Arguments::parse(content, "Blog", &["dir"], diagnostics)?;
+    let dir_arg = args.required("dir")?;
+    let dir = dir_arg.value.trim().to_string();
+    let blog_dir = generation_dirs.in_source(&dir);
+    let blog_files = get_files_in_dir(&blog_dir).ok_or_else(|| {
+        ParseError::new(
+            format!("could not read blog directory `{}`", blog_dir.display()),
+            dir_arg.raw,
+        )
+    })?;
     let blog_posts = blog_files
         .iter()
-        .filter_map(|x| parse_blog_file(x, generation_dirs))
+        .filter_map(|x| Some((x, parse_blog_file(x, generation_dirs, scope, diagnostics)?)))
         .collect::<Vec<_>>();
-    Some(TemplateType::Blog(CMSBlog { posts: blog_posts }))
+    let blog_posts = unique_slugs(blog_posts, diagnostics);
+    let drafts = generation_dirs.get_config().drafts;
+    let (blog_posts, next_publication) = publish(blog_posts, Utc::now(), drafts);
+    Ok(TemplateType::Blog(CMSBlog {
+        dir,
+        posts: blog_posts,
+        next_publication,
+    }))
 }
 
 #[cfg(test)]
@@ -66,6 +317,110 @@ mod test {
         assert!(files.contains(&std::path::PathBuf::from(
             "sample/blog_files/my_first_toy.cms".to_string()
         )));
+        assert!(files.contains(&std::path::PathBuf::from(
+            "sample/blog_files/writing_in_markdown.md".to_string()
+        )));
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(tmp_dir.path().join("image.png"), "").unwrap();
+        assert!(get_files_in_dir(tmp_dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_markdown_blog_file() {
+        let path = Path::new("sample/blog_files/writing_in_markdown.md");
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let post = parse_blog_file(
+            path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(post.title, "Writing in Markdown");
+        assert_eq!(post.slug, "writing-in-markdown");
+        assert_eq!(post.tags, ["code"]);
+        let [TemplateType::Markdown { content, images }] = post.templates.as_slice() else {
+            panic!("a Markdown post is a single Markdown template");
+        };
+        assert!(content.starts_with("Posts in a blog directory"));
+        assert!(images.is_empty(), "code spans are not images");
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("post.md");
+        let contents = "+++\ntitle = \"Toml\"\ndate = 2024-01-01\n+++\n\
+            ![sample](sample.jpg) ![missing](missing.png) ![remote](https://example.com/a.png)\n";
+        std::fs::write(&path, contents).unwrap();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let post = parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+        assert_eq!(post.title, "Toml");
+        let [TemplateType::Markdown { images, .. }] = post.templates.as_slice() else {
+            panic!("a Markdown post is a single Markdown template");
+        };
+        assert_eq!(images.keys().collect::<Vec<_>>(), ["sample.jpg"]);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].message.contains("missing.png"));
+        assert_eq!(entries[0].line, 5);
+    }
+
+    #[test]
+    fn test_get_slug() {
+        assert_eq!(
+            get_slug(Path::new("blog_files/learning_code.cms")),
+            Some("learning-code".to_string())
+        );
+        assert_eq!(
+            get_slug(Path::new("My First Toy!.cms")),
+            Some("my-first-toy".to_string())
+        );
+        assert_eq!(get_slug(Path::new("_.cms")), None);
+    }
+
+    #[test]
+    fn test_unique_slugs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let files = [
+            "hello.md",
+            "hello.cms",
+            "My Post.cms",
+            "my-post.cms",
+            "_.cms",
+        ]
+        .map(|x| tmp_dir.path().join(x));
+        for path in &files {
+            std::fs::write(path, "+++\ntitle = \"Post\"\ndate = 2023-12-31\n+++\n").unwrap();
+        }
+        let posts = files
+            .iter()
+            .filter_map(|x| {
+                let post =
+                    parse_blog_file(x, &generation_dirs, &Default::default(), &mut diagnostics)?;
+                Some((x, post))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(posts.len(), 4);
+        let posts = unique_slugs(posts, &mut diagnostics);
+        let slugs = posts.iter().map(|x| x.slug.as_str()).collect::<Vec<_>>();
+        assert_eq!(slugs, ["my-post", "hello"]);
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].message.contains("no letters or digits"));
+        assert_eq!(entries[0].file, files[4]);
+        assert!(entries[1].message.contains("same slug `hello` as"));
+        assert_eq!(entries[1].file, files[0]);
+        assert!(entries[2].message.contains("same slug `my-post` as"));
+        assert_eq!(entries[2].file, files[3]);
     }
 
     #[test]
@@ -73,16 +428,182 @@ mod test {
         let path = PathBuf::from("sample/blog_files/my_first_toy.cms");
         assert!(path.exists());
         let generation_dirs = TempGenerationDirs::default();
-        let blog = parse_blog_file(&path, &generation_dirs).unwrap();
-        assert!(blog.templates.get_title().unwrap().contains("toy"));
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let blog = parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+        assert!(blog.title.contains("toy"));
+        assert_eq!(blog.slug, "my-first-toy");
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_blog_file_front_matter() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("post.cms");
+        let contents = "+++\ntitle = \"Front\"\ndate = 2024-01-01\nslug = \"First!\"\n\
+            tags = [\"rust\"]\ndraft = true\n+++\n{{Tags|ignored}}\n{{Paragraph|x}}{{Dat|y}}";
+        std::fs::write(&path, contents).unwrap();
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let post = parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+        assert_eq!(post.title, "Front");
+        assert_eq!(post.slug, "first");
+        assert_eq!(post.tags, ["rust"]);
+        assert!(post.draft);
+        assert!(post.updated.is_none());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].line, 9,
+            "lines are counted from the start of the file"
+        );
+
+        let path = tmp_dir.path().join("broken.cms");
+        std::fs::write(&path, "+++\ntitle = \"Broken\"\ndate = 2024\n+++\n").unwrap();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        assert!(parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics
+        )
+        .is_none());
+        assert!(entries[1].message.contains("`date` must be a date"));
+        assert_eq!(entries[1].line, 3);
+    }
+
+    #[test]
+    fn test_blog_file_without_date() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("post.cms");
+        std::fs::write(&path, "{{Title|Undated}}\n{{Dat|2023-12-31}}").unwrap();
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        assert!(parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics
+        )
+        .is_none());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file, path);
+        assert_eq!(entries[0].line, 2);
+        assert!(entries[1].message.contains("no `date` or `{{Date|...}}`"));
+    }
+
+    #[test]
+    fn test_publish() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let files = [
+            ("old.cms", "{{Title|Old}}{{Date|2024-01-01}}"),
+            ("draft.cms", "{{Title|Draft}}{{Date|2024-01-02}}{{Draft}}"),
+            ("soon.cms", "{{Title|Soon}}{{Date|2024-03-01}}"),
+            ("later.cms", "{{Title|Later}}{{Date|2024-06-01}}"),
+        ];
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let posts = files
+            .iter()
+            .map(|(name, content)| {
+                let path = tmp_dir.path().join(name);
+                std::fs::write(&path, content).unwrap();
+                parse_blog_file(
+                    &path,
+                    &generation_dirs,
+                    &Default::default(),
+                    &mut diagnostics,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(entries.is_empty());
+        assert!(posts[1].draft);
+
+        let now = "2024-02-01T00:00:00Z".parse().unwrap();
+        let (published, next) = publish(posts.clone(), now, false);
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].slug, "old");
+        assert_eq!(next, Some(posts[2].post_date));
+        let (published, next) = publish(posts, now, true);
+        assert_eq!(published.len(), 4);
+        assert!(next.is_none());
+    }
+
+    #[test]
+    fn test_blog_file_cache() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("post.cms");
+        std::fs::write(&path, "{{Title|First}}\n{{Dat|2023-12-31}}").unwrap();
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        assert!(parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics
+        )
+        .is_none());
+        assert!(parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics
+        )
+        .is_none());
+        assert_eq!(entries.len(), 4, "cached diagnostics are reported again");
+
+        std::fs::write(&path, "{{Title|Second}}\n{{Date|2023-12-31}}").unwrap();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let post = parse_blog_file(
+            &path,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
+        assert_eq!(post.templates.get_title().unwrap(), "Second");
+        assert!(entries.is_empty());
     }
 
     #[test]
     fn test_parse_blog() {
         let content = Some("blog_files/");
         let generation_dirs = TempGenerationDirs::default();
-        let blog = parse_blog(content, &generation_dirs).unwrap();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("index.cms"), "", &mut entries);
+        let blog = parse_blog(
+            content,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        )
+        .unwrap();
         let blog = blog.get_blog().unwrap();
         assert!(!blog.posts.is_empty());
+        assert_eq!(blog.dir, "blog_files/");
+        let error = parse_blog(
+            Some("no_exist/"),
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        );
+        assert!(error.is_err());
     }
 }
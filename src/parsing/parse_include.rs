@@ -0,0 +1,164 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::types::generation_dirs::GenerationDirs;
+
+use super::{
+    arguments::Arguments,
+    diagnostics::{require_content, Diagnostics, ParseError},
+    parse_templates,
+    scope::Scope,
+    ParseElements,
+};
+
+/// Resolves `path` relative to the directory of the file `including`, giving a path relative
+/// to the source directory. Returns `None` if the result would leave the source directory,
+/// since the watcher would not notice when such a file changes.
+pub fn resolve(including: &Path, path: &str, source_dir: &Path) -> Option<PathBuf> {
+    let base = including
+        .parent()
+        .and_then(|x| x.strip_prefix(source_dir).ok())
+        .unwrap_or(Path::new(""));
+    let mut resolved = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::Normal(x) => resolved.push(x),
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+/// Parses another source file in place of `{{Include|file}}`.
+pub fn parse_include<'a>(
+    content: Option<&'a str>,
+    generation_dirs: &impl GenerationDirs,
+    scope: &Scope,
+    diagnostics: &mut Diagnostics,
+) -> Result<ParseElements, ParseError<'a>> {
+    let content = require_content(content, "Include")?;
+    let args = Arguments::parse(content, "Include", &["file"], diagnostics)?;
+    let file_arg = args.required("file")?;
+    let file = file_arg.value.trim();
+    let source_dir = generation_dirs.get_source_dir();
+    let resolved = resolve(diagnostics.file(), file, &source_dir).ok_or_else(|| {
+        ParseError::new(
+            format!("`{file}` is outside of the source directory"),
+            file_arg.raw,
+        )
+    })?;
+    let path = generation_dirs.in_source(&resolved.to_string_lossy());
+
+    let mut including = scope.including.clone();
+    including.push(diagnostics.file().to_path_buf());
+    if including.contains(&path) {
+        let chain = including
+            .iter()
+            .chain([&path])
+            .map(|x| {
+                x.strip_prefix(&source_dir)
+                    .unwrap_or(x)
+                    .display()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        return Err(ParseError::new(
+            format!("`{file}` includes itself ({chain})"),
+            file_arg.raw,
+        ));
+    }
+
+    let contents = generation_dirs
+        .get_cache()
+        .read_to_string(&path)
+        .map_err(|e| {
+            ParseError::new(
+                format!("could not read `{}`: {e}", path.display()),
+                file_arg.raw,
+            )
+        })?;
+    let scope = Scope {
+        including,
+        ..scope.clone()
+    };
+    let (templates, pages) = parse_templates(
+        &contents,
+        generation_dirs,
+        &scope,
+        &mut diagnostics.for_file(&path, &contents),
+    );
+    Ok(ParseElements::Expanded(templates, pages))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::generation_dirs::TempGenerationDirs;
+
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let source_dir = Path::new("sample/");
+        let index = Path::new("sample/index.cms");
+        let nested = Path::new("sample/pages/contact.cms");
+        assert_eq!(
+            resolve(index, "pages/contact.cms", source_dir).unwrap(),
+            Path::new("pages/contact.cms")
+        );
+        assert_eq!(
+            resolve(nested, "./footer.cms", source_dir).unwrap(),
+            Path::new("pages/footer.cms")
+        );
+        assert_eq!(
+            resolve(nested, "../index.cms", source_dir).unwrap(),
+            Path::new("index.cms")
+        );
+        assert!(resolve(index, "../Cargo.toml", source_dir).is_none());
+        assert!(resolve(index, "/etc/passwd", source_dir).is_none());
+    }
+
+    #[test]
+    fn test_include() {
+        const CONTENT: &str = "{{Include|pages/contact.cms}}";
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics =
+            Diagnostics::new(Path::new("sample/index.cms"), CONTENT, &mut entries);
+        let (_, pages) = parse_templates(
+            CONTENT,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        );
+        assert!(pages.contains_key("contact"));
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_include_errors() {
+        const CONTENT: &str = "{{Include|contact.cms}}\n{{Include|missing.cms}}";
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics =
+            Diagnostics::new(Path::new("sample/pages/contact.cms"), CONTENT, &mut entries);
+        parse_templates(
+            CONTENT,
+            &generation_dirs,
+            &Default::default(),
+            &mut diagnostics,
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].message,
+            "`contact.cms` includes itself (pages/contact.cms -> pages/contact.cms)"
+        );
+        assert_eq!(entries[0].span, 10..21);
+        assert!(entries[1].message.starts_with("could not read"));
+    }
+}
@@ -1,141 +1,358 @@
-use chrono::{DateTime, Local, TimeZone, Utc};
-use log::info;
+use chrono::{DateTime, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
 
 use crate::{
-    img_handling::{get_img_as_b64_url, get_img_b64_size},
-    types::{generation_dirs::GenerationDirs, link_type::LinkType, template_type::TemplateType},
+    generation::is_supported_language,
+    types::{
+        cms_blog::slugify,
+        cms_image::{CMSImage, ImageAlign},
+        generation_dirs::GenerationDirs,
+        link_type::{LinkType, LINK_TYPE_NAMES},
+        template_type::TemplateType,
+    },
 };
 
-pub fn parse_title(content: Option<&str>) -> Option<TemplateType> {
-    let content = content?;
-    Some(TemplateType::Title {
-        title: content.to_string(),
+use super::{
+    arguments::{split_key, Arguments},
+    diagnostics::{require_content, Diagnostics, ParseError},
+    tokenizer::{split_args, split_first_arg, unescape, Arg},
+};
+
+pub fn parse_title(content: Option<&str>) -> Result<TemplateType, ParseError<'_>> {
+    let content = require_content(content, "Title")?;
+    Ok(TemplateType::Title {
+        title: unescape(content)?,
     })
 }
 
-pub fn parse_paragraph(content: Option<&str>) -> Option<TemplateType> {
-    let content = content?;
-    Some(TemplateType::Paragraph {
-        content: content.to_string(),
+pub fn parse_paragraph(content: Option<&str>) -> Result<TemplateType, ParseError<'_>> {
+    let content = require_content(content, "Paragraph")?;
+    Ok(TemplateType::Paragraph {
+        content: unescape(content)?,
     })
 }
 
-pub fn parse_links(content: Option<&str>) -> Option<TemplateType> {
-    let content = content?;
-    let link_pairs: Vec<&str> = content.split(',').collect();
-    let link_pairs: Vec<(LinkType, String)> = link_pairs
-        .iter()
-        .filter_map(|x| {
-            let pair: Vec<&str> = x.split(':').collect();
-            if pair.len() == 2 {
-                let link_type = match pair[0] {
-                    "Github" => Some(LinkType::Github),
-                    _ => None,
-                };
-                if let Some(link_type) = link_type {
-                    let pair = (link_type, pair[1].to_string());
-                    return Some(pair);
-                }
-                return None;
+pub fn parse_markdown(content: Option<&str>) -> Result<TemplateType, ParseError<'_>> {
+    let content = require_content(content, "Markdown")?;
+    Ok(TemplateType::Markdown {
+        content: unescape(content)?,
+        images: Default::default(),
+    })
+}
+
+pub fn parse_html(content: Option<&str>) -> Result<TemplateType, ParseError<'_>> {
+    let content = require_content(content, "Html")?;
+    Ok(TemplateType::Html {
+        content: unescape(content)?,
+    })
+}
+
+/// Whether `part` is the port of `url`, e.g. `8080/` of `https://example.com`.
+fn is_port_of(url: &str, part: &str) -> bool {
+    let Some((_, host)) = url.split_once("://") else {
+        return false;
+    };
+    let port = part.split('/').next().unwrap_or_default();
+    !host.contains('/') && !port.is_empty() && port.chars().all(|x| x.is_ascii_digit())
+}
+
+/// Joins the parts of a link that were split inside a URL scheme like `https://` or
+/// before a port, so that URLs do not have to be quoted.
+fn join_url_schemes(parts: &[Arg]) -> Vec<String> {
+    let mut joined: Vec<String> = Vec::new();
+    for part in parts {
+        match joined.last_mut() {
+            Some(last) if part.value.starts_with("//") || is_port_of(last, &part.value) => {
+                last.push(':');
+                last.push_str(part.value.trim_end());
             }
-            None
-        })
-        .collect::<Vec<(LinkType, String)>>();
-    let link_pairs: std::collections::HashMap<_, _> = link_pairs.into_iter().collect();
+            _ => joined.push(part.value.trim().to_string()),
+        }
+    }
+    joined
+}
+
+pub fn parse_links<'a>(
+    content: Option<&'a str>,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    let content = require_content(content, "Links")?;
+    let mut link_pairs: Vec<(LinkType, String)> = Vec::new();
+    for link in Arguments::parse_list(content, "Links", &["links"], diagnostics)? {
+        let parts = split_args(link.raw, ':')?;
+        let Some((name, rest)) = parts.split_first() else {
+            continue;
+        };
+        let rest = join_url_schemes(rest);
+        if rest.is_empty() {
+            diagnostics.warning("expected a link in the form `Type:target`", link.raw);
+            continue;
+        }
+        let name_value = name.value.trim();
+        if name_value == "Custom" {
+            let (label, url, icon) = match rest.as_slice() {
+                [label, url] => (label, url, None),
+                [label, url, icon] => (label, url, Some(icon.clone())),
+                _ => {
+                    diagnostics.warning(
+                        "expected a link in the form `Custom:label:url` or `Custom:label:url:icon`",
+                        link.raw,
+                    );
+                    continue;
+                }
+            };
+            let label = label.clone();
+            link_pairs.push((LinkType::Custom { label, icon }, url.clone()));
+            continue;
+        }
+        let Ok(link_type) = name_value.parse::<LinkType>() else {
+            diagnostics.warning(
+                format!(
+                    "unknown link type `{}`, expected one of {}",
+                    name_value,
+                    LINK_TYPE_NAMES.join(", ")
+                ),
+                name.raw,
+            );
+            continue;
+        };
+        if rest.len() != 1 {
+            diagnostics.warning("expected a link in the form `Type:target`", link.raw);
+            continue;
+        }
+        link_pairs.push((link_type, rest[0].clone()));
+    }
     if !link_pairs.is_empty() {
-        return Some(TemplateType::Links { links: link_pairs });
+        return Ok(TemplateType::Links { links: link_pairs });
     }
-    None
+    Err(ParseError::new("`Links` contains no valid links", content))
 }
 
-pub fn parse_navbar(content: Option<&str>) -> Option<TemplateType> {
-    let content = content?;
-    let paths = content
-        .split(',')
-        .map(|x| x.to_string())
+pub fn parse_navbar<'a>(
+    content: Option<&'a str>,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    let content = require_content(content, "Navbar")?;
+    let paths = Arguments::parse_list(content, "Navbar", &["pages"], diagnostics)?
+        .into_iter()
+        .map(|x| x.value)
         .collect::<Vec<_>>();
-    Some(TemplateType::Navbar { paths })
+    Ok(TemplateType::Navbar { paths })
+}
+
+/// The URL of the image at `url`, relative to the source directory, and whether it has to be
+/// copied. Images that are small enough are inlined as data URLs instead.
+pub fn load_image(
+    url: &str,
+    size: Option<u32>,
+    generation_dirs: &impl GenerationDirs,
+) -> Result<(String, bool), String> {
+    let images = &generation_dirs.get_config().images;
+    let inline_url = generation_dirs
+        .inline_img(url, size.unwrap_or(images.default_size))
+        .map_err(|e| format!("could not read image `{url}`: {e}"))?;
+    match inline_url.len() <= images.max_inline_size {
+        true => Ok((inline_url, false)),
+        false => Ok((url.to_string(), true)),
+    }
 }
 
-pub fn parse_image(
-    content: Option<&str>,
+/// Parses `{{Image|src=...|size=...|alt=...|caption=...|link=...|align=...}}`,
+/// or the same arguments by position, e.g. `{{Image|sample.jpg,200,Me}}`.
+/// Everything but `src` is optional.
+pub fn parse_image<'a>(
+    content: Option<&'a str>,
     generation_dirs: &impl GenerationDirs,
-) -> Option<TemplateType> {
-    let content = content?;
-    let args = content.split(',').collect::<Vec<_>>();
-    let mut url = args.first()?.to_string();
-    let size = args.get(1);
-    let size = match size {
-        Some(x) => str::parse::<u32>(x).ok(),
-        _ => None,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    const PARAMS: &[&str] = &["src", "size", "alt", "caption", "link", "align"];
+    let content = require_content(content, "Image")?;
+    let args = Arguments::parse(content, "Image", PARAMS, diagnostics)?;
+    let url_arg = args.required("src")?;
+    let size = args.value::<u32>("size", "a positive integer")?;
+    let alt = match args.string("alt") {
+        Some(alt) => alt,
+        None => {
+            diagnostics.warning(
+                format!("image `{}` has no alt text, add `alt=...`", url_arg.value),
+                url_arg.raw,
+            );
+            String::new()
+        }
     };
-    let source_url = generation_dirs.in_source(&url);
-    let b64_size = get_img_b64_size(source_url.as_path(), size).ok()?;
-    const MAXIMUM_B64_SIZE: usize = 1000;
-    let mut copy_asset = true;
-    if b64_size <= MAXIMUM_B64_SIZE {
-        url = get_img_as_b64_url(source_url.as_path(), size).ok()?;
-        copy_asset = false;
-    }
-    Some(TemplateType::Image {
+    let align = args.value::<ImageAlign>("align", "`left`, `center` or `right`")?;
+    let (url, copy_asset) = load_image(&url_arg.value, size, generation_dirs)
+        .map_err(|e| ParseError::new(e, url_arg.raw))?;
+    Ok(TemplateType::Image(CMSImage {
         url,
         copy_asset,
         size,
-    })
+        alt,
+        caption: args.string("caption"),
+        link: args.string("link"),
+        align,
+    }))
 }
 
-pub fn parse_name(content: Option<&str>) -> Option<TemplateType> {
-    Some(TemplateType::Name {
-        name: content?.to_string(),
+pub fn parse_name(content: Option<&str>) -> Result<TemplateType, ParseError<'_>> {
+    Ok(TemplateType::Name {
+        name: unescape(require_content(content, "Name")?)?,
     })
 }
 
-pub fn parse_nkr_cms_info() -> Option<TemplateType> {
-    Some(TemplateType::NRCMSInfo {
-        text:
-            "This website was automatically generated with <a href=\"https://github.com/naresh97/nr-cms\">NR-CMS.</a>",
+pub fn parse_nkr_cms_info(
+    generation_dirs: &impl GenerationDirs,
+) -> Result<TemplateType, ParseError<'static>> {
+    Ok(TemplateType::NRCMSInfo {
+        text: generation_dirs.get_config().nr_cms_info.clone(),
     })
 }
 
-fn parse_date_time_from_str(content: &str, format: &str) -> Option<DateTime<Utc>> {
-    let mut content = content.to_string();
-    if !content.contains(':') {
-        info!("Date provided: {content} does not contain time, appending 00:00:00");
-        content += " 00:00:00";
+/// Date and time formats without an offset, which are read in the time zone of the site.
+const LOCAL_DATE_TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Parses an RFC 3339 date with an offset, e.g. `2023-12-31T18:00:00+01:00`, or a date
+/// without one, e.g. `2023-12-31 18:00` or `2023-12-31`, which is read in `timezone`.
+/// A date without a time means midnight.
+pub fn parse_date_time(value: &str, timezone: Tz) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.into());
     }
-    let date_time = chrono::NaiveDateTime::parse_from_str(&content, format).unwrap();
-    let date_time = Local.from_local_datetime(&date_time).single()?;
-    Some(date_time.into())
+    let local = LOCAL_DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+            date.and_hms_opt(0, 0, 0)
+        })
+        .ok_or_else(|| {
+            format!(
+                "invalid date `{value}`, expected e.g. `2023-12-31`, `2023-12-31 18:00` \
+                or `2023-12-31T18:00:00+01:00`"
+            )
+        })?;
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(date_time) => Ok(date_time.with_timezone(&Utc)),
+        // When clocks are turned back, the earlier of the two times is meant
+        LocalResult::Ambiguous(date_time, _) => Ok(date_time.with_timezone(&Utc)),
+        LocalResult::None => Err(format!(
+            "`{value}` does not exist in the time zone {timezone}, \
+            the clocks skip it when changing to daylight saving time"
+        )),
+    }
+}
+
+pub fn parse_date<'a>(
+    content: Option<&'a str>,
+    generation_dirs: &impl GenerationDirs,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    let content = require_content(content, "Date")?;
+    let args = Arguments::parse(content, "Date", &["date"], diagnostics)?;
+    let date = args.required("date")?;
+    let date = parse_date_time(&date.value, generation_dirs.get_config().timezone)
+        .map_err(|e| ParseError::new(e, date.raw))?;
+    Ok(TemplateType::Date { date })
+}
+
+/// Parses `{{Draft}}`, which keeps a blog post from being published.
+pub fn parse_draft<'a>(
+    content: Option<&'a str>,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    if let Some(content) = content {
+        diagnostics.warning("`Draft` takes no content, it is ignored", content);
+    }
+    Ok(TemplateType::Draft)
 }
 
-pub fn parse_date(content: Option<&str>) -> Option<TemplateType> {
-    let date = parse_date_time_from_str(content?, "%Y-%m-%d %H:%M:%S")?;
-    Some(TemplateType::Date { date })
+/// Parses `{{Tags|rust,embedded}}`. Tags that only differ in case or punctuation are merged.
+pub fn parse_tags<'a>(
+    content: Option<&'a str>,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    let content = require_content(content, "Tags")?;
+    let mut tags: Vec<String> = Vec::new();
+    for tag in Arguments::parse_list(content, "Tags", &["tags"], diagnostics)? {
+        let name = tag.value.trim();
+        if name.is_empty() {
+            continue;
+        }
+        if slugify(name).is_empty() {
+            diagnostics.warning(
+                format!("tag `{name}` needs at least one letter or digit"),
+                tag.raw,
+            );
+            continue;
+        }
+        if tags.iter().any(|x| slugify(x) == slugify(name)) {
+            diagnostics.warning(format!("tag `{name}` is given more than once"), tag.raw);
+            continue;
+        }
+        tags.push(name.to_string());
+    }
+    if tags.is_empty() {
+        return Err(ParseError::new("`Tags` contains no tags", content));
+    }
+    Ok(TemplateType::Tags { tags })
 }
 
-pub fn parse_code(content: Option<&str>) -> Option<TemplateType> {
-    let content = content?;
-    Some(TemplateType::Code {
-        code: content.to_string(),
+/// Parses `{{Code|language=rust|code}}`. The language can also be given without a name,
+/// as long as it is a supported one, e.g. `{{Code|rust|code}}`. Everything after the first
+/// argument is the code, so it may contain further `|`. Otherwise the whole content is the code.
+pub fn parse_code<'a>(
+    content: Option<&'a str>,
+    diagnostics: &mut Diagnostics,
+) -> Result<TemplateType, ParseError<'a>> {
+    const PARAMS: &[&str] = &["language"];
+    let content = require_content(content, "Code")?;
+    let (language, code) = match split_first_arg(content, '|')? {
+        Some((first, code)) if split_key(first).is_some() => {
+            let args = Arguments::parse(first, "Code", PARAMS, diagnostics)?;
+            (args.string("language"), code)
+        }
+        Some((first, code)) if is_supported_language(first) => (Some(first.to_string()), code),
+        Some((first, code))
+            if first
+                .split_once('=')
+                .is_some_and(|(key, _)| PARAMS.contains(&key.trim())) =>
+        {
+            diagnostics.warning(
+                "arguments are written without spaces around `=`, e.g. `language=rust`",
+                first,
+            );
+            (None, code)
+        }
+        _ => (None, content),
+    };
+    Ok(TemplateType::Code {
+        code: unescape(code)?,
+        language: language.map(|x| x.trim().to_ascii_lowercase()),
     })
 }
 
 #[cfg(test)]
 mod test {
+    use std::path::Path;
 
-    #[test]
-    fn test_parse_date() {
-        parse_date_time_from_str("2023-12-31", "%Y-%m-%d %H:%M:%S").unwrap();
-        parse_date_time_from_str("2023-12-31 01:02:03", "%Y-%m-%d %H:%M:%S").unwrap();
-        parse_date(Some("2023-12-31")).unwrap();
-    }
-
-    use crate::types::generation_dirs::TempGenerationDirs;
+    use crate::types::{
+        diagnostic::Diagnostic,
+        generation_dirs::TempGenerationDirs,
+        site_config::{ImageConfig, SiteConfig},
+    };
 
     use super::*;
     #[test]
     fn test_simple_parsing() {
         const TEST: &str = "test";
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
         assert_eq!(parse_name(Some(TEST)).unwrap().get_name().unwrap(), TEST);
         assert_eq!(
             parse_paragraph(Some(TEST))
@@ -145,40 +362,317 @@ mod test {
             TEST
         );
         assert_eq!(parse_title(Some(TEST)).unwrap().get_title().unwrap(), TEST);
-        assert!(parse_nkr_cms_info().unwrap().get_nr_cms_info().is_some());
+        assert_eq!(
+            parse_markdown(Some(TEST)).unwrap().get_markdown().unwrap(),
+            TEST
+        );
+        assert_eq!(
+            parse_html(Some("<br/>")).unwrap().get_html().unwrap(),
+            "<br/>"
+        );
+        let generation_dirs = TempGenerationDirs::default();
+        assert_eq!(
+            parse_nkr_cms_info(&generation_dirs)
+                .unwrap()
+                .get_nr_cms_info()
+                .unwrap(),
+            generation_dirs.get_config().nr_cms_info
+        );
+        assert_eq!(
+            parse_paragraph(Some(r"Handlebars: \{{name\}}"))
+                .unwrap()
+                .get_paragraph()
+                .unwrap(),
+            "Handlebars: {{name}}"
+        );
+        assert_eq!(
+            parse_code(Some("{{{let sum = |a, b| a + b;}}}"), &mut diagnostics)
+                .unwrap()
+                .get_code()
+                .unwrap()
+                .0,
+            "let sum = |a, b| a + b;"
+        );
+        let code =
+            parse_code(Some("Rust|{{{let sum = |a, b| a + b;}}}"), &mut diagnostics).unwrap();
+        let (code, language) = code.get_code().unwrap();
+        assert_eq!(code, "let sum = |a, b| a + b;");
+        assert_eq!(language.unwrap(), "rust");
+        let code = parse_code(Some("language=Rust|x = a|b"), &mut diagnostics).unwrap();
+        let (code, language) = code.get_code().unwrap();
+        assert_eq!(code, "x = a|b");
+        assert_eq!(language.unwrap(), "rust");
+        let code = parse_code(Some("\nx = a|b"), &mut diagnostics).unwrap();
+        let (code, language) = code.get_code().unwrap();
+        assert_eq!(code, "\nx = a|b");
+        assert!(language.is_none());
+        let code = parse_code(Some(r"a \| b|c"), &mut diagnostics).unwrap();
+        assert_eq!(code.get_code().unwrap().0, "a | b|c");
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_parse_code_arguments() {
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
+        let code = parse_code(Some(r#"language="Rust"|a|b"#), &mut diagnostics).unwrap();
+        assert_eq!(
+            code.get_code().unwrap(),
+            (&"a|b".to_string(), Some(&"rust".to_string()))
+        );
+        let code = parse_code(Some("lang=rust|a|b"), &mut diagnostics).unwrap();
+        assert_eq!(code.get_code().unwrap(), (&"a|b".to_string(), None));
+        let code = parse_code(Some("language = rust|a"), &mut diagnostics).unwrap();
+        assert_eq!(code.get_code().unwrap(), (&"a".to_string(), None));
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0]
+            .message
+            .contains("unknown argument `lang` for `Code`"));
+        assert!(entries[1].message.contains("without spaces around `=`"));
+    }
+    #[test]
+    fn test_parse_date_time() {
+        let expected = Utc.with_ymd_and_hms(2023, 12, 31, 17, 0, 0).unwrap();
+        let berlin = chrono_tz::Europe::Berlin;
+        for value in [
+            "2023-12-31T18:00:00+01:00",
+            "2023-12-31 18:00:00+01:00",
+            "2023-12-31T17:00:00Z",
+            " 2023-12-31 17:00:00 ",
+            "2023-12-31T17:00",
+        ] {
+            assert_eq!(parse_date_time(value, Tz::UTC), Ok(expected), "{value}");
+        }
+        assert_eq!(parse_date_time("2023-12-31 18:00", berlin), Ok(expected));
+        assert_eq!(
+            parse_date_time("2023-12-31T18:00:00+01:00", chrono_tz::Asia::Tokyo),
+            Ok(expected)
+        );
+        assert_eq!(
+            parse_date_time("2024-01-01", berlin),
+            Ok(Utc.with_ymd_and_hms(2023, 12, 31, 23, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse_date_time("2024-10-27 02:30", berlin),
+            Ok(Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0).unwrap())
+        );
+        let error = parse_date_time("2024-03-31 02:30", berlin).unwrap_err();
+        assert!(error.contains("does not exist in the time zone Europe/Berlin"));
+        for value in ["31.12.2023", "2023-13-01", "2023-12-31 25:00", ""] {
+            let error = parse_date_time(value, Tz::UTC).unwrap_err();
+            assert!(error.starts_with("invalid date"), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let generation_dirs = TempGenerationDirs::default();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
+        parse_date(Some("2023-12-31"), &generation_dirs, &mut diagnostics).unwrap();
+        parse_date(Some("date=2023-12-31"), &generation_dirs, &mut diagnostics).unwrap();
+        let error = parse_date(Some("31.12.2023"), &generation_dirs, &mut diagnostics)
+            .err()
+            .unwrap();
+        assert_eq!(error.at.unwrap(), "31.12.2023");
+        assert!(parse_date(None, &generation_dirs, &mut diagnostics).is_err());
+        assert!(entries.is_empty());
     }
+
     #[test]
     fn test_parse_links() {
         const LINKS: &str = "Github:A,None:B";
-        let links = parse_links(Some(LINKS)).unwrap();
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), LINKS, &mut entries);
+        let links = parse_links(Some(LINKS), &mut diagnostics).unwrap();
         let links = links.get_links().unwrap();
-        assert!(links.contains_key(&LinkType::Github));
-        assert_eq!(links.get(&LinkType::Github).unwrap(), "A");
+        assert_eq!(links, &[(LinkType::Github, "A".to_string())]);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].message.starts_with("unknown link type `None`"));
+        assert_eq!(entries[0].span, 9..13);
         const LINKS_BROKEN: &str = "GithubA,NoneB";
-        let links = parse_links(Some(LINKS_BROKEN));
-        assert!(links.is_none());
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), LINKS_BROKEN, &mut entries);
+        let links = parse_links(Some(LINKS_BROKEN), &mut diagnostics);
+        assert!(links.is_err());
+        assert_eq!(entries.len(), 2);
+        const LINKS_QUOTED: &str = r#"Github:"a,b",Github\:B"#;
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), LINKS_QUOTED, &mut entries);
+        let links = parse_links(Some(LINKS_QUOTED), &mut diagnostics).unwrap();
+        assert_eq!(links.get_links().unwrap()[0].1, "a,b");
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_links_types() {
+        const LINKS: &str = r#"Mastodon:https://mastodon.social/@me,Email:me@example.com,
+        Custom:"My blog":https://blog.example.com:blog.svg,Custom:Shop:https://shop.example.com,
+        Custom:Broken,Custom:Local:http://localhost:8080/:local.svg,
+        Custom:Blog:https://example.com:8080/"#;
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), LINKS, &mut entries);
+        let links = parse_links(Some(LINKS), &mut diagnostics).unwrap();
+        let links = links.get_links().unwrap();
+        assert_eq!(
+            links[0],
+            (
+                LinkType::Mastodon,
+                "https://mastodon.social/@me".to_string()
+            )
+        );
+        assert_eq!(links[1], (LinkType::Email, "me@example.com".to_string()));
+        assert_eq!(
+            links[2],
+            (
+                LinkType::Custom {
+                    label: "My blog".to_string(),
+                    icon: Some("blog.svg".to_string())
+                },
+                "https://blog.example.com".to_string()
+            )
+        );
+        assert_eq!(links[3].1, "https://shop.example.com");
+        assert_eq!(
+            links[4],
+            (
+                LinkType::Custom {
+                    label: "Local".to_string(),
+                    icon: Some("local.svg".to_string())
+                },
+                "http://localhost:8080/".to_string()
+            )
+        );
+        assert_eq!(
+            links[5],
+            (
+                LinkType::Custom {
+                    label: "Blog".to_string(),
+                    icon: None
+                },
+                "https://example.com:8080/".to_string()
+            )
+        );
+        assert_eq!(links.len(), 6);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].message.contains("`Custom:label:url`"));
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        const TAGS: &str = "Rust, embedded,rust,!!";
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), TAGS, &mut entries);
+        let tags = parse_tags(Some(TAGS), &mut diagnostics).unwrap();
+        assert_eq!(tags.get_tags().unwrap(), &["Rust", "embedded"]);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].message.contains("more than once"));
+        assert!(entries[1].message.contains("at least one letter or digit"));
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
+        assert!(parse_tags(Some(" , "), &mut diagnostics).is_err());
     }
 
     #[test]
     fn test_parse_navbar() {
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
         const PAGES: &str = "a,b,c";
-        let pages = parse_navbar(Some(PAGES)).unwrap();
+        let pages = parse_navbar(Some(PAGES), &mut diagnostics).unwrap();
         let pages = pages.get_navbar().unwrap();
         assert_eq!(pages.len(), 3);
+        const PAGES_QUOTED: &str = r#"a,"b, c",d\,e"#;
+        let pages = parse_navbar(Some(PAGES_QUOTED), &mut diagnostics).unwrap();
+        let pages = pages.get_navbar().unwrap();
+        assert_eq!(pages, &["a", "b, c", "d,e"]);
+        const PAGES_NAMED: &str = "pages=a,b";
+        let pages = parse_navbar(Some(PAGES_NAMED), &mut diagnostics).unwrap();
+        assert_eq!(pages.get_navbar().unwrap(), &["a", "b"]);
+        assert!(entries.is_empty());
+    }
+
+    fn parse_image_with_diagnostics<'a>(
+        content: &'a str,
+        generation_dirs: &TempGenerationDirs,
+    ) -> (Result<TemplateType, ParseError<'a>>, Vec<Diagnostic>) {
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), content, &mut entries);
+        let image = parse_image(Some(content), generation_dirs, &mut diagnostics);
+        (image, entries)
     }
 
     #[test]
     fn test_parse_image() {
         const IMG: &str = "sample.jpg";
         let generation_dirs = TempGenerationDirs::default();
-        let image = parse_image(Some(IMG), &generation_dirs).unwrap();
+        let (image, entries) = parse_image_with_diagnostics(IMG, &generation_dirs);
+        let image = image.unwrap();
         let image = image.get_image().unwrap();
-        assert!(image.1);
-        assert_eq!(image.0, IMG);
+        assert!(image.copy_asset);
+        assert_eq!(image.url, IMG);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].message.contains("no alt text"));
+        const IMG_NAMED: &str = "src=sample.jpg|size=10|alt=Me|align=left";
+        let (image, entries) = parse_image_with_diagnostics(IMG_NAMED, &generation_dirs);
+        let image = image.unwrap();
+        let image = image.get_image().unwrap();
+        assert_eq!(image.size, Some(10));
+        assert_eq!(image.alt, "Me");
+        assert_eq!(image.align, Some(ImageAlign::Left));
+        assert!(entries.is_empty());
         const IMG_SIZE: &str = "sample.jpg,10";
-        let image = parse_image(Some(IMG_SIZE), &generation_dirs).unwrap();
+        let (image, _) = parse_image_with_diagnostics(IMG_SIZE, &generation_dirs);
+        let image = image.unwrap();
+        let image = image.get_image().unwrap();
+        assert_eq!(image.size.unwrap(), 10);
+        assert!(!image.copy_asset);
+        const IMG_BAD_SIZE: &str = "sample.jpg,big";
+        let (error, _) = parse_image_with_diagnostics(IMG_BAD_SIZE, &generation_dirs);
+        assert_eq!(error.err().unwrap().at.unwrap(), "big");
+        const IMG_NO_EXIST: &str = "no_exist.jpg,,Missing";
+        let (error, _) = parse_image_with_diagnostics(IMG_NO_EXIST, &generation_dirs);
+        assert!(error
+            .err()
+            .unwrap()
+            .message
+            .contains("could not read image"));
+    }
+
+    #[test]
+    fn test_parse_image_options() {
+        const IMG: &str = r#"sample.jpg,,"Me, smiling",A caption,https://example.com,right"#;
+        let generation_dirs = TempGenerationDirs::default();
+        let (image, entries) = parse_image_with_diagnostics(IMG, &generation_dirs);
+        let image = image.unwrap();
+        let image = image.get_image().unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(image.size, None);
+        assert_eq!(image.alt, "Me, smiling");
+        assert_eq!(image.caption.as_deref(), Some("A caption"));
+        assert_eq!(image.link.as_deref(), Some("https://example.com"));
+        assert_eq!(image.align, Some(ImageAlign::Right));
+
+        const IMG_BAD_ALIGN: &str = "sample.jpg,,Me,,,middle";
+        let (error, _) = parse_image_with_diagnostics(IMG_BAD_ALIGN, &generation_dirs);
+        assert_eq!(error.err().unwrap().at.unwrap(), "middle");
+        const IMG_TOO_MANY: &str = "sample.jpg,,Me,,,left,extra";
+        let (image, entries) = parse_image_with_diagnostics(IMG_TOO_MANY, &generation_dirs);
+        assert!(image.is_ok());
+        assert_eq!(entries[0].span, 22..27);
+    }
+
+    #[test]
+    fn test_parse_image_inline_policy() {
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            images: ImageConfig {
+                max_inline_size: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let (image, _) = parse_image_with_diagnostics("sample.jpg,10", &generation_dirs);
+        let image = image.unwrap();
         let image = image.get_image().unwrap();
-        assert_eq!(image.2.unwrap(), 10);
-        assert!(!image.1);
+        assert!(image.copy_asset);
+        assert_eq!(image.url, "sample.jpg");
     }
 }
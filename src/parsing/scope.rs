@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+use super::user_templates::UserTemplates;
+
+/// Everything that parsing a part of a source file depends on, besides the text itself.
+#[derive(Clone, Default)]
+pub struct Scope {
+    pub user_templates: UserTemplates,
+    /// The files whose `{{Include|...}}` is being parsed, outermost first
+    pub including: Vec<PathBuf>,
+}
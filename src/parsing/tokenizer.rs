@@ -0,0 +1,278 @@
+use super::diagnostics::ParseError;
+
+const OPENING_BRACE: &str = "{{";
+const CLOSING_BRACE: &str = "}}";
+const RAW_OPENING: &str = "{{{";
+const RAW_CLOSING: &str = "}}}";
+
+/// Characters that can be escaped with a backslash anywhere in a template.
+const STRUCTURAL_ESCAPES: &[char] = &['{', '}', '|'];
+/// Characters that can additionally be escaped inside argument lists.
+const ARGUMENT_ESCAPES: &[char] = &['{', '}', '|', ',', ':', '"', '='];
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Char(usize, char),
+    Escaped(usize, char),
+    Raw(usize, &'a str),
+    Open(usize),
+    Close(usize),
+}
+
+/// Splits content into braces, escape sequences, raw blocks and plain characters.
+struct Scanner<'a> {
+    content: &'a str,
+    escapes: &'static [char],
+    position: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(content: &'a str, escapes: &'static [char]) -> Self {
+        Scanner {
+            content,
+            escapes,
+            position: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<Token<'a>, ParseError<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.position;
+        let rest = &self.content[start..];
+        let mut chars = rest.chars();
+        let current = chars.next()?;
+        let token = if current == '\\' && chars.next().is_some_and(|x| self.escapes.contains(&x)) {
+            let escaped = rest[1..].chars().next()?;
+            self.position += 1 + escaped.len_utf8();
+            Token::Escaped(start, escaped)
+        } else if let Some(raw_rest) = rest.strip_prefix(RAW_OPENING) {
+            let Some(end) = raw_rest.find(RAW_CLOSING) else {
+                self.position = self.content.len();
+                return Some(Err(ParseError::new(
+                    "unclosed raw block `{{{`, expected a matching `}}}`",
+                    &rest[..RAW_OPENING.len()],
+                )));
+            };
+            self.position += RAW_OPENING.len() + end + RAW_CLOSING.len();
+            Token::Raw(start, &raw_rest[..end])
+        } else if rest.starts_with(OPENING_BRACE) {
+            self.position += OPENING_BRACE.len();
+            Token::Open(start)
+        } else if rest.starts_with(CLOSING_BRACE) {
+            self.position += CLOSING_BRACE.len();
+            Token::Close(start)
+        } else {
+            self.position += current.len_utf8();
+            Token::Char(start, current)
+        };
+        Some(Ok(token))
+    }
+}
+
+pub fn get_tags(content: &str) -> Result<Vec<&str>, ParseError<'_>> {
+    let mut templates: Vec<(usize, usize)> = Vec::new();
+    let mut scope_count = 0;
+    let mut current_template: (usize, usize) = (0, 0);
+    for token in Scanner::new(content, STRUCTURAL_ESCAPES) {
+        match token? {
+            Token::Open(index) => {
+                if scope_count == 0 {
+                    current_template.0 = index + OPENING_BRACE.len();
+                }
+                scope_count += 1;
+            }
+            Token::Close(index) => {
+                if scope_count == 0 {
+                    return Err(ParseError::new(
+                        "unexpected `}}` without a matching `{{`",
+                        &content[index..index + CLOSING_BRACE.len()],
+                    ));
+                }
+                scope_count -= 1;
+                if scope_count == 0 {
+                    current_template.1 = index;
+                    templates.push(current_template);
+                }
+            }
+            _ => (),
+        }
+    }
+    if scope_count != 0 {
+        let opening = current_template.0 - OPENING_BRACE.len();
+        return Err(ParseError::new(
+            "unclosed `{{`, expected a matching `}}`",
+            &content[opening..opening + OPENING_BRACE.len()],
+        ));
+    }
+    Ok(templates
+        .iter()
+        .map(|x| &content[x.0..x.1])
+        .collect::<Vec<_>>())
+}
+
+/// Resolves escape sequences and raw blocks in the content of a template.
+pub fn unescape(content: &str) -> Result<String, ParseError<'_>> {
+    let mut result = String::with_capacity(content.len());
+    for token in Scanner::new(content, STRUCTURAL_ESCAPES) {
+        match token? {
+            Token::Char(_, x) | Token::Escaped(_, x) => result.push(x),
+            Token::Raw(_, raw) => result.push_str(raw),
+            Token::Open(_) => result.push_str(OPENING_BRACE),
+            Token::Close(_) => result.push_str(CLOSING_BRACE),
+        }
+    }
+    Ok(result)
+}
+
+/// A single argument of a template. `raw` is the argument as written in the source,
+/// `value` has its quotes, escape sequences and raw blocks resolved.
+#[derive(Debug)]
+pub struct Arg<'a> {
+    pub raw: &'a str,
+    pub value: String,
+}
+
+/// Splits an argument list on `separator`, ignoring separators that are escaped,
+/// quoted with `"..."`, inside a raw block or inside a nested template.
+pub fn split_args(content: &str, separator: char) -> Result<Vec<Arg<'_>>, ParseError<'_>> {
+    let mut args = Vec::new();
+    let mut arg_start = 0;
+    let mut value = String::new();
+    let mut depth = 0;
+    let mut quote_start: Option<usize> = None;
+    for token in Scanner::new(content, ARGUMENT_ESCAPES) {
+        match token? {
+            Token::Char(index, x) if x == separator && depth == 0 && quote_start.is_none() => {
+                args.push(Arg {
+                    raw: &content[arg_start..index],
+                    value: std::mem::take(&mut value),
+                });
+                arg_start = index + x.len_utf8();
+            }
+            Token::Char(index, '"') if depth == 0 => {
+                quote_start = match quote_start {
+                    Some(_) => None,
+                    None => Some(index),
+                };
+            }
+            Token::Char(_, x) | Token::Escaped(_, x) => value.push(x),
+            Token::Raw(_, raw) => value.push_str(raw),
+            Token::Open(_) => {
+                depth += 1;
+                value.push_str(OPENING_BRACE);
+            }
+            Token::Close(_) => {
+                depth -= 1;
+                value.push_str(CLOSING_BRACE);
+            }
+        }
+    }
+    if let Some(quote_start) = quote_start {
+        return Err(ParseError::new(
+            "unclosed `\"`, expected a matching `\"`",
+            &content[quote_start..quote_start + 1],
+        ));
+    }
+    args.push(Arg {
+        raw: &content[arg_start..],
+        value,
+    });
+    Ok(args)
+}
+
+/// Splits off the first argument before `separator`, like `split_args`, and returns it with
+/// the rest as written. Quotes are not special here, since the rest is usually a body like
+/// code that may contain unbalanced ones.
+pub fn split_first_arg(
+    content: &str,
+    separator: char,
+) -> Result<Option<(&str, &str)>, ParseError<'_>> {
+    let mut depth = 0;
+    for token in Scanner::new(content, ARGUMENT_ESCAPES) {
+        match token? {
+            Token::Char(index, x) if x == separator && depth == 0 => {
+                return Ok(Some((&content[..index], &content[index + x.len_utf8()..])));
+            }
+            Token::Open(_) => depth += 1,
+            Token::Close(_) => depth -= 1,
+            _ => (),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_get_tags() {
+        let test = "{{hello}} {{world}} {{outer {{inner}}}}";
+        let tags = get_tags(test).unwrap();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags[0], "hello");
+        assert_eq!(tags[1], "world");
+        assert_eq!(tags[2], "outer {{inner}}");
+
+        let test = "{{hello}} {{there";
+        let error = get_tags(test).unwrap_err();
+        assert!(error.message.contains("unclosed"));
+        assert_eq!(error.at.unwrap().as_ptr(), test[10..].as_ptr());
+
+        let test = "{{hello}} there}}";
+        let error = get_tags(test).unwrap_err();
+        assert!(error.message.contains("unexpected"));
+        assert_eq!(error.at.unwrap().as_ptr(), test[15..].as_ptr());
+    }
+
+    #[test]
+    fn test_get_tags_escaped() {
+        let test = r"{{Paragraph|use \{{name\}} or {{{ {{raw}} }}}}} \{{ignored\}}";
+        let tags = get_tags(test).unwrap();
+        assert_eq!(tags, [r"Paragraph|use \{{name\}} or {{{ {{raw}} }}}"]);
+
+        let test = "{{Paragraph|{{{never closed}}";
+        let error = get_tags(test).unwrap_err();
+        assert!(error.message.contains("raw block"));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(
+            unescape(r"use \{{name\}} \| {{{ {{raw}}, \| }}} C:\Users").unwrap(),
+            r"use {{name}} |  {{raw}}, \|  C:\Users"
+        );
+        assert_eq!(unescape(r#"printf("\"\n")"#).unwrap(), r#"printf("\"\n")"#);
+    }
+
+    #[test]
+    fn test_split_args() {
+        let args = split_args(r#"a,"b, c",d\,e,{{{f,g}}},{{Nested|h,i}}"#, ',').unwrap();
+        let values = args.iter().map(|x| x.value.as_str()).collect::<Vec<_>>();
+        assert_eq!(values, ["a", "b, c", "d,e", "f,g", "{{Nested|h,i}}"]);
+        assert_eq!(args[1].raw, r#""b, c""#);
+
+        let args = split_args(r#""Me: \"myself\"":target"#, ':').unwrap();
+        assert_eq!(args[0].value, r#"Me: "myself""#);
+        assert_eq!(args[1].value, "target");
+
+        let error = split_args(r#"a,"b"#, ',').unwrap_err();
+        assert_eq!(error.at.unwrap(), "\"");
+    }
+
+    #[test]
+    fn test_split_first_arg() {
+        assert_eq!(
+            split_first_arg(r#"rust|let a = "|";"#, '|').unwrap(),
+            Some(("rust", r#"let a = "|";"#))
+        );
+        assert_eq!(
+            split_first_arg(r"a \| b|c", '|').unwrap(),
+            Some((r"a \| b", "c"))
+        );
+        assert_eq!(split_first_arg("{{{a|b}}}", '|').unwrap(), None);
+        assert_eq!(split_first_arg("{{Nested|a}}", '|').unwrap(), None);
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::BTreeMap,
+    hash::{Hash, Hasher},
+};
+
+use super::{
+    arguments::Arguments,
+    diagnostics::{require_content, Diagnostics, ParseError},
+    tokenizer::split_args,
+};
+
+/// Names that cannot be redefined, because they are built into the parser.
+pub const BUILT_IN_TEMPLATES: &[&str] = &[
+    "Navbar",
+    "Title",
+    "Paragraph",
+    "Markdown",
+    "Html",
+    "Links",
+    "NKR-CMS-INFO",
+    "Image",
+    "Name",
+    "Page",
+    "Date",
+    "Blog",
+    "Code",
+    "Define",
+    "Include",
+    "Tags",
+    "Draft",
+];
+
+const PARAMS_PREFIX: &str = "params=";
+
+/// A template declared with `{{Define|Name|params=a,b|body}}`.
+#[derive(Clone, Hash)]
+struct UserTemplate {
+    params: Vec<String>,
+    body: String,
+}
+
+/// The user-defined templates visible at some point of a source file,
+/// together with the ones that are currently being expanded.
+#[derive(Clone, Default)]
+pub struct UserTemplates {
+    templates: BTreeMap<String, UserTemplate>,
+    expanding: Vec<String>,
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '-')
+}
+
+/// The text that is replaced by the argument for `param`.
+fn placeholder(param: &str) -> String {
+    format!("{{{{${param}}}}}")
+}
+
+impl UserTemplates {
+    /// Declares the template defined by the content of a `{{Define|...}}`.
+    pub fn define<'a>(
+        &mut self,
+        content: Option<&'a str>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<(), ParseError<'a>> {
+        let content = require_content(content, "Define")?;
+        let Some((name, rest)) = content.split_once('|') else {
+            return Err(ParseError::new("expected `{{Define|Name|body}}`", content));
+        };
+        let name_raw = name;
+        let name = name.trim();
+        if !is_valid_name(name) {
+            return Err(ParseError::new(
+                format!("`{name}` is not a valid template name, use letters, digits, `_` and `-`"),
+                name_raw,
+            ));
+        }
+        if BUILT_IN_TEMPLATES.contains(&name) {
+            return Err(ParseError::new(
+                format!("`{name}` is a built-in template and cannot be redefined"),
+                name_raw,
+            ));
+        }
+        let (params, body) = match rest.trim_start().strip_prefix(PARAMS_PREFIX) {
+            Some(rest) => {
+                let (params, body) = rest.split_once('|').unwrap_or((rest, ""));
+                let params = split_args(params, ',')?;
+                for param in params.iter().filter(|x| !is_valid_name(x.value.trim())) {
+                    diagnostics.warning(
+                        format!("`{}` is not a valid parameter name", param.value),
+                        param.raw,
+                    );
+                }
+                let params = params
+                    .into_iter()
+                    .map(|x| x.value.trim().to_string())
+                    .filter(|x| is_valid_name(x))
+                    .collect::<Vec<_>>();
+                (params, body)
+            }
+            None => (Vec::new(), rest),
+        };
+        for (index, _) in body.match_indices("{{$") {
+            let used = &body[index + 3..];
+            let used = &used[..used.find("}}").unwrap_or(used.len())];
+            if !params.iter().any(|x| x == used) {
+                diagnostics.warning(
+                    format!("`{name}` has no parameter `{used}`"),
+                    &body[index..index + 3 + used.len()],
+                );
+            }
+        }
+        if self.templates.contains_key(name) {
+            diagnostics.warning(
+                format!("template `{name}` is defined more than once"),
+                name_raw,
+            );
+        }
+        let template = UserTemplate {
+            params,
+            body: body.to_string(),
+        };
+        self.templates.insert(name.to_string(), template);
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+
+    /// Substitutes the arguments of an invocation into the body of the template `name`.
+    /// Returns the expanded source together with the templates to parse it with.
+    pub fn expand<'a>(
+        &self,
+        name: &str,
+        content: Option<&'a str>,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<(String, UserTemplates), ParseError<'a>> {
+        let Some(template) = self.templates.get(name) else {
+            return Err(ParseError::without_location(format!(
+                "unknown template `{name}`"
+            )));
+        };
+        if self.expanding.iter().any(|x| x == name) {
+            let chain = [self.expanding.as_slice(), &[name.to_string()]]
+                .concat()
+                .join(" -> ");
+            return Err(ParseError::without_location(format!(
+                "template `{name}` uses itself ({chain})"
+            )));
+        }
+        let params = template
+            .params
+            .iter()
+            .map(|x| x.as_str())
+            .collect::<Vec<_>>();
+        let args = match content {
+            Some(content) => Some(Arguments::parse(content, name, &params, diagnostics)?),
+            None => None,
+        };
+        let mut expanded = template.body.clone();
+        for param in &params {
+            let value = args.as_ref().and_then(|x| x.get(param)).map(|x| x.raw);
+            if value.is_none() && expanded.contains(&placeholder(param)) {
+                diagnostics.warning(
+                    format!("`{name}` is missing the argument `{param}`"),
+                    content.unwrap_or_default(),
+                );
+            }
+            expanded = expanded.replace(&placeholder(param), value.unwrap_or_default());
+        }
+        let mut scope = self.clone();
+        scope.expanding.push(name.to_string());
+        Ok((expanded, scope))
+    }
+
+    /// Changes whenever any of the templates changes, so that cached results can be invalidated.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = std::hash::DefaultHasher::new();
+        for (name, template) in &self.templates {
+            name.hash(&mut hasher);
+            template.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::types::diagnostic::Diagnostic;
+
+    use super::*;
+
+    const CARD: &str = "Card|params=title,image|{{Paragraph|{{$title}}}}{{Image|src={{$image}}}}";
+
+    fn define(user_templates: &mut UserTemplates, content: &str) -> Vec<Diagnostic> {
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), content, &mut entries);
+        if let Err(e) = user_templates.define(Some(content), &mut diagnostics) {
+            diagnostics.report(e);
+        }
+        entries
+    }
+
+    #[test]
+    fn test_define() {
+        let mut user_templates = UserTemplates::default();
+        assert!(define(&mut user_templates, CARD).is_empty());
+        assert!(user_templates.contains("Card"));
+        assert_eq!(user_templates.templates["Card"].params, ["title", "image"]);
+
+        let entries = define(&mut user_templates, "Image|{{Paragraph|x}}");
+        assert!(entries[0].message.contains("built-in template"));
+        let entries = define(&mut user_templates, "Broken");
+        assert!(entries[0]
+            .message
+            .contains("expected `{{Define|Name|body}}`"));
+        let entries = define(&mut user_templates, "Other|params=a|{{Paragraph|{{$b}}}}");
+        assert_eq!(entries[0].message, "`Other` has no parameter `b`");
+        assert_eq!(entries[0].span, 27..31);
+        let fingerprint = user_templates.fingerprint();
+        define(&mut user_templates, "Other|params=a|{{Paragraph|{{$a}}}}");
+        assert_ne!(user_templates.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut user_templates = UserTemplates::default();
+        define(&mut user_templates, CARD);
+        let mut entries = Vec::new();
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
+        let (expanded, scope) = user_templates
+            .expand(
+                "Card",
+                Some("title=Hello, world|image=a.jpg"),
+                &mut diagnostics,
+            )
+            .unwrap();
+        assert_eq!(expanded, "{{Paragraph|Hello, world}}{{Image|src=a.jpg}}");
+        assert_eq!(scope.expanding, ["Card"]);
+        let (expanded, _) = user_templates
+            .expand("Card", Some("Hello|a.jpg"), &mut diagnostics)
+            .unwrap();
+        assert_eq!(expanded, "{{Paragraph|Hello}}{{Image|src=a.jpg}}");
+        assert!(entries.is_empty());
+
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
+        let (expanded, _) = user_templates
+            .expand("Card", Some("title=Hi"), &mut diagnostics)
+            .unwrap();
+        assert_eq!(expanded, "{{Paragraph|Hi}}{{Image|src=}}");
+        assert!(entries[0].message.contains("missing the argument `image`"));
+
+        let mut diagnostics = Diagnostics::new(Path::new("test.cms"), "", &mut entries);
+        let error = scope.expand("Card", None, &mut diagnostics).err().unwrap();
+        assert_eq!(error.message, "template `Card` uses itself (Card -> Card)");
+    }
+}
@@ -0,0 +1,395 @@
+use std::{
+    io::Write,
+    path::{Component, Path, PathBuf},
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    assets, generation::generate_website::build_website, types::generation_dirs::GenerationDirs,
+    watcher,
+};
+
+const EVENTS_PATH: &str = "/__nr_cms/events";
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Keeps track of the browser tabs listening for rebuilds and the outcome of the last build.
+#[derive(Default)]
+struct LiveReload {
+    listeners: Mutex<Vec<Sender<String>>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl LiveReload {
+    fn subscribe(&self) -> Receiver<String> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        if let Some(error) = self.last_error.lock().unwrap().as_ref() {
+            let _ = tx.send(format_event("build-error", error));
+        }
+        self.listeners.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn notify(&self, build_result: Result<(), String>) {
+        let event = match &build_result {
+            Ok(()) => format_event("reload", ""),
+            Err(e) => format_event("build-error", e),
+        };
+        *self.last_error.lock().unwrap() = build_result.err();
+        self.listeners
+            .lock()
+            .unwrap()
+            .retain(|listener| listener.send(event.clone()).is_ok());
+    }
+}
+
+fn format_event(name: &str, data: &str) -> String {
+    let data = data
+        .lines()
+        .map(|line| format!("data: {line}\n"))
+        .collect::<String>();
+    let data = if data.is_empty() {
+        "data: \n".to_string()
+    } else {
+        data
+    };
+    format!("event: {name}\n{data}\n")
+}
+
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|x| std::str::from_utf8(x).ok())
+            .and_then(|x| u8::from_str_radix(x, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn resolve_path(generation_dir: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(path);
+    let relative = Path::new(path.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|x| !matches!(x, Component::Normal(_)))
+    {
+        return None;
+    }
+    let mut resolved = generation_dir.join(relative);
+    if resolved.is_dir() {
+        resolved.push("index.html");
+    }
+    Some(resolved)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|x| x.to_str())
+        .map(|x| x.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("xml") => "application/xml; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+fn inject_live_reload(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(index) => format!(
+            "{}{}{}",
+            &html[..index],
+            assets::scripts::LIVE_RELOAD,
+            &html[index..]
+        ),
+        None => format!("{html}{}", assets::scripts::LIVE_RELOAD),
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("static header is valid")
+}
+
+fn serve_events(request: Request, live_reload: &LiveReload) {
+    let events = live_reload.subscribe();
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+    if writer.write_all(head.as_bytes()).is_err() || writer.flush().is_err() {
+        return;
+    }
+    loop {
+        let message = match events.recv_timeout(KEEP_ALIVE_INTERVAL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => ": keep-alive\n\n".to_string(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if writer.write_all(message.as_bytes()).is_err() || writer.flush().is_err() {
+            log::debug!("Live reload client disconnected");
+            return;
+        }
+    }
+}
+
+fn serve_file(request: Request, generation_dir: &Path) {
+    let path = resolve_path(generation_dir, request.url());
+    let content = path.as_ref().and_then(|x| std::fs::read(x).ok());
+    let result = match (path, content) {
+        (Some(path), Some(content)) => {
+            let content_type = content_type(&path);
+            let content = if content_type.starts_with("text/html") {
+                inject_live_reload(&String::from_utf8_lossy(&content)).into_bytes()
+            } else {
+                content
+            };
+            request.respond(
+                Response::from_data(content)
+                    .with_header(header("Content-Type", content_type))
+                    .with_header(header("Cache-Control", "no-store")),
+            )
+        }
+        _ => {
+            log::debug!("Not found: {}", request.url());
+            request.respond(Response::from_string("404 Not Found").with_status_code(404))
+        }
+    };
+    if let Err(e) = result {
+        log::debug!("Could not send response: {e}");
+    }
+}
+
+fn handle_request(request: Request, generation_dir: &Path, live_reload: &LiveReload) {
+    log::trace!("{} {}", request.method(), request.url());
+    if *request.method() != Method::Get {
+        let _ =
+            request.respond(Response::from_string("405 Method Not Allowed").with_status_code(405));
+        return;
+    }
+    if request.url() == EVENTS_PATH {
+        serve_events(request, live_reload);
+    } else {
+        serve_file(request, generation_dir);
+    }
+}
+
+fn handle_requests(server: &Server, generation_dir: PathBuf, live_reload: Arc<LiveReload>) {
+    for request in server.incoming_requests() {
+        let generation_dir = generation_dir.clone();
+        let live_reload = live_reload.clone();
+        std::thread::spawn(move || handle_request(request, &generation_dir, &live_reload));
+    }
+}
+
+/// Serves the website until `true` is sent on the cancellation token.
+pub fn serve(
+    mut generation_dirs: impl GenerationDirs,
+    address: &str,
+    port: u16,
+    cancellation_token: Option<Receiver<bool>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let server = Arc::new(Server::http((address, port)).map_err(|e| e.to_string())?);
+    let live_reload = Arc::new(LiveReload::default());
+    let build = build_website(&mut generation_dirs);
+    live_reload.notify(build.result);
+
+    let generation_dir = generation_dirs.get_generation_dir();
+    let watcher_live_reload = live_reload.clone();
+    let (cancel_watcher, watcher_cancellation_token) = std::sync::mpsc::channel::<bool>();
+    let watcher = std::thread::spawn(move || {
+        let result = watcher::watch_with_callback(
+            generation_dirs,
+            Some(watcher_cancellation_token),
+            build.next_publication,
+            move |result| watcher_live_reload.notify(result),
+        );
+        if let Err(e) = result {
+            log::error!("Could not initialize watcher: {e}");
+        }
+    });
+    if let Some(cancellation_token) = cancellation_token {
+        let server = server.clone();
+        std::thread::spawn(move || {
+            while let Ok(cancel) = cancellation_token.recv() {
+                if cancel {
+                    server.unblock();
+                    return;
+                }
+            }
+        });
+    }
+
+    log::info!("Serving website at http://{address}:{port}/");
+    handle_requests(&server, generation_dir, live_reload);
+    let _ = cancel_watcher.send(true);
+    let _ = watcher.join();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Read},
+        net::TcpStream,
+    };
+
+    use crate::types::generation_dirs::TempGenerationDirs;
+
+    use super::*;
+
+    fn start_test_server(generation_dir: PathBuf) -> (std::net::SocketAddr, Arc<LiveReload>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let live_reload = Arc::new(LiveReload::default());
+        let server_live_reload = live_reload.clone();
+        std::thread::spawn(move || handle_requests(&server, generation_dir, server_live_reload));
+        (address, live_reload)
+    }
+
+    fn get(address: std::net::SocketAddr, path: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        stream
+    }
+
+    #[test]
+    fn test_format_event() {
+        assert_eq!(format_event("reload", ""), "event: reload\ndata: \n\n");
+        assert_eq!(
+            format_event("build-error", "first\nsecond"),
+            "event: build-error\ndata: first\ndata: second\n\n"
+        );
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let generation_dirs = TempGenerationDirs::default();
+        let generation_dir = generation_dirs.get_generation_dir();
+        assert_eq!(
+            resolve_path(&generation_dir, "/").unwrap(),
+            generation_dir.join("index.html")
+        );
+        assert_eq!(
+            resolve_path(&generation_dir, "/my%20image.jpg?x=1").unwrap(),
+            generation_dir.join("my image.jpg")
+        );
+        assert!(resolve_path(&generation_dir, "/../secret").is_none());
+        assert!(resolve_path(&generation_dir, "/a/%2e%2e/%2e%2e/secret").is_none());
+    }
+
+    #[test]
+    fn test_inject_live_reload() {
+        let html = inject_live_reload("<html><body>hi</body></html>");
+        assert!(html.contains(EVENTS_PATH));
+        assert!(html.ends_with("</body></html>"));
+        assert!(inject_live_reload("hi").contains(EVENTS_PATH));
+    }
+
+    #[test]
+    fn test_live_reload_notify() {
+        let live_reload = LiveReload::default();
+        let events = live_reload.subscribe();
+        live_reload.notify(Err("broken".to_string()));
+        assert!(events.recv().unwrap().contains("data: broken"));
+        let late_events = live_reload.subscribe();
+        assert!(late_events
+            .recv()
+            .unwrap()
+            .starts_with("event: build-error"));
+        live_reload.notify(Ok(()));
+        assert!(events.recv().unwrap().starts_with("event: reload"));
+        assert!(live_reload.last_error.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_serve_files() {
+        let mut generation_dirs = TempGenerationDirs::default();
+        build_website(&mut generation_dirs).result.unwrap();
+        let (address, _) = start_test_server(generation_dirs.get_generation_dir());
+
+        let mut response = String::new();
+        get(address, "/").read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("text/html"));
+        assert!(response.contains(EVENTS_PATH));
+
+        let mut response = String::new();
+        get(address, "/no_exist.html")
+            .read_to_string(&mut response)
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_serve_cancellation() {
+        let (cancel, cancellation_token) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            serve(
+                TempGenerationDirs::default(),
+                "127.0.0.1",
+                0,
+                Some(cancellation_token),
+            )
+            .unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        cancel.send(true).unwrap();
+        for _ in 0..20 {
+            if handle.is_finished() {
+                handle.join().unwrap();
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        panic!("Server did not shut down");
+    }
+
+    #[test]
+    fn test_serve_events() {
+        let generation_dirs = TempGenerationDirs::default();
+        let (address, live_reload) = start_test_server(generation_dirs.get_generation_dir());
+        let mut events = BufReader::new(get(address, EVENTS_PATH));
+        let mut line = String::new();
+        events.read_line(&mut line).unwrap();
+        assert!(line.starts_with("HTTP/1.1 200"));
+        while line != "\r\n" {
+            line.clear();
+            events.read_line(&mut line).unwrap();
+        }
+        live_reload.notify(Ok(()));
+        line.clear();
+        events.read_line(&mut line).unwrap();
+        assert_eq!(line, "event: reload\n");
+    }
+}
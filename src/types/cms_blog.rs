@@ -1,14 +1,121 @@
+use std::collections::BTreeMap;
+
 use chrono::{self, Utc};
 
-use super::template_type::TemplateType;
+use super::{cms_image::CMSImage, template_type::TemplateType};
+
+/// Turns text into a lowercase, URL-safe name, e.g. `My First Toy!` into `my-first-toy`.
+pub fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|x: char| !x.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
 
 #[derive(Clone)]
 pub struct BlogPost {
+    /// URL-safe name of the post, given in the front matter or derived from its file name
+    pub slug: String,
+    pub title: String,
     pub post_date: chrono::DateTime<Utc>,
+    /// Date of the last change worth mentioning
+    pub updated: Option<chrono::DateTime<Utc>>,
+    /// The tags of the front matter or of `{{Tags|...}}`, as they were written
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    /// Shown instead of an excerpt in the list layout and in feeds
+    pub summary: Option<String>,
+    /// Image shown above the post
+    pub cover: Option<CMSImage>,
+    /// Marked as draft in the front matter or with `{{Draft}}`, only published with `--drafts`
+    pub draft: bool,
     pub templates: Vec<TemplateType>,
 }
 
 #[derive(Clone)]
 pub struct CMSBlog {
+    /// The directory of the blog posts, relative to the source directory
+    pub dir: String,
     pub posts: Vec<BlogPost>,
+    /// Date of the earliest post that is scheduled, but not published yet
+    pub next_publication: Option<chrono::DateTime<Utc>>,
+}
+
+/// A tag together with the posts that have it, newest first.
+pub struct BlogTag<'a> {
+    /// The tag as it was first written
+    pub name: &'a str,
+    pub slug: String,
+    pub posts: Vec<&'a BlogPost>,
+}
+
+impl CMSBlog {
+    /// All tags of the blog, sorted by their slug. Tags with the same slug, e.g. `Rust` and
+    /// `rust`, are the same tag.
+    pub fn tags(&self) -> Vec<BlogTag<'_>> {
+        let mut posts = self.posts.iter().collect::<Vec<_>>();
+        posts.sort_by_key(|x| std::cmp::Reverse(x.post_date));
+        let mut tags: BTreeMap<String, BlogTag> = BTreeMap::new();
+        for post in posts {
+            for name in &post.tags {
+                let slug = slugify(name);
+                let tag = tags.entry(slug.clone()).or_insert_with(|| BlogTag {
+                    name,
+                    slug,
+                    posts: Vec::new(),
+                });
+                tag.posts.push(post);
+            }
+        }
+        tags.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn post(slug: &str, day: u32, tags: &[&str]) -> BlogPost {
+        BlogPost {
+            slug: slug.to_string(),
+            title: slug.to_string(),
+            post_date: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            updated: None,
+            tags: tags.iter().map(|x| x.to_string()).collect(),
+            author: None,
+            summary: None,
+            cover: None,
+            draft: false,
+            templates: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("My First Toy!"), "my-first-toy");
+        assert_eq!(slugify("C++ / Rust"), "c-rust");
+        assert_eq!(slugify("!!"), "");
+    }
+
+    #[test]
+    fn test_tags() {
+        let blog = CMSBlog {
+            dir: "blog/".to_string(),
+            posts: Vec::from([
+                post("old", 1, &["Rust", "embedded"]),
+                post("new", 2, &["rust"]),
+                post("untagged", 3, &[]),
+            ]),
+            next_publication: None,
+        };
+        let tags = blog.tags();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].slug, "embedded");
+        assert_eq!(tags[1].name, "rust");
+        let slugs = tags[1].posts.iter().map(|x| x.slug.as_str());
+        assert_eq!(slugs.collect::<Vec<_>>(), ["new", "old"]);
+    }
 }
@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+/// Where an image is placed horizontally.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageAlign {
+    Left,
+    Center,
+    Right,
+}
+
+impl FromStr for ImageAlign {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "left" => Ok(ImageAlign::Left),
+            "center" => Ok(ImageAlign::Center),
+            "right" => Ok(ImageAlign::Right),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ImageAlign {
+    pub fn class_name(self) -> &'static str {
+        match self {
+            ImageAlign::Left => "image-left",
+            ImageAlign::Center => "image-center",
+            ImageAlign::Right => "image-right",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CMSImage {
+    /// Path relative to the source directory, or a data URL if the image is inlined
+    pub url: String,
+    pub copy_asset: bool,
+    pub size: Option<u32>,
+    /// Describes the image to screen readers, empty for purely decorative images
+    pub alt: String,
+    pub caption: Option<String>,
+    /// URL that the image links to
+    pub link: Option<String>,
+    pub align: Option<ImageAlign>,
+}
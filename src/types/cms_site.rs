@@ -1,9 +1,9 @@
 use std::collections::HashMap;
 
-use super::{cms_page::CMSPage, template_type::TemplateType};
+use super::{cms_page::CMSPage, diagnostic::Diagnostic, template_type::TemplateType};
 
 pub struct CMSSite {
-    pub original_content: String,
     pub templates: Vec<TemplateType>,
     pub pages: HashMap<String, CMSPage>,
+    pub diagnostics: Vec<Diagnostic>,
 }
@@ -0,0 +1,125 @@
+use std::{
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a source file, pointing at the byte span that caused it.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: PathBuf,
+    pub span: Range<usize>,
+    pub line: usize,
+    pub column: usize,
+    pub source_line: String,
+}
+
+impl Diagnostic {
+    pub fn new(
+        severity: Severity,
+        message: impl Into<String>,
+        file: &Path,
+        source: &str,
+        span: Range<usize>,
+    ) -> Self {
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map(|x| x + 1).unwrap_or(0);
+        let line_end = source[start..]
+            .find('\n')
+            .map(|x| x + start)
+            .unwrap_or(source.len());
+        Diagnostic {
+            severity,
+            message: message.into(),
+            file: file.to_path_buf(),
+            line: source[..start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            source_line: source[line_start..line_end].trim_end().to_string(),
+            span,
+        }
+    }
+
+    /// Renders the diagnostic the way rustc does, with a snippet of the offending line.
+    pub fn render(&self) -> String {
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let padding = " ".repeat(self.column - 1);
+        let remaining = self
+            .source_line
+            .chars()
+            .count()
+            .saturating_sub(self.column - 1);
+        let span_width = self.span.len().clamp(1, remaining.max(1));
+        let markers = "^".repeat(span_width);
+        format!(
+            "{}: {}\n{gutter}--> {}:{}:{}\n{gutter} |\n{line_number} | {}\n{gutter} | {padding}{markers}",
+            self.severity,
+            self.message,
+            self.file.display(),
+            self.line,
+            self.column,
+            self.source_line,
+        )
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_and_column() {
+        let source = "{{Title|hi}}\n  {{Nonsense}}\n";
+        let diagnostic = Diagnostic::new(
+            Severity::Error,
+            "unknown template",
+            Path::new("index.cms"),
+            source,
+            15..27,
+        );
+        assert_eq!(diagnostic.line, 2);
+        assert_eq!(diagnostic.column, 3);
+        assert_eq!(diagnostic.source_line, "  {{Nonsense}}");
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "{{Title|hi}}\n  {{Nonsense}}\n";
+        let diagnostic = Diagnostic::new(
+            Severity::Warning,
+            "unknown template",
+            Path::new("index.cms"),
+            source,
+            15..27,
+        );
+        assert_eq!(
+            diagnostic.render(),
+            "warning: unknown template\n --> index.cms:2:3\n  |\n2 |   {{Nonsense}}\n  |   ^^^^^^^^^^^^"
+        );
+        let empty = Diagnostic::new(Severity::Error, "empty", Path::new("a"), "", 0..0);
+        assert!(empty.render().ends_with("| ^"));
+    }
+}
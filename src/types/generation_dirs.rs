@@ -1,12 +1,45 @@
 use std::path::PathBuf;
 
-use crate::img_handling;
+use crate::{
+    build_cache::{self, BuildCache, CACHE_DIR},
+    img_handling,
+};
 
-use super::program_args::ProgramArgs;
+use super::{
+    program_args::ProgramArgs,
+    site_config::{SiteConfig, CONFIG_FILE},
+};
+
+/// Images smaller than this are not worth adding to a `srcset`.
+const MIN_SRCSET_SIZE: u32 = 100;
+
+/// An image that was copied into the generation directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopiedImage {
+    /// Path relative to the generation directory
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An image shown at the size of `image`, with `variants` of other sizes for a `srcset`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResponsiveImage {
+    pub image: CopiedImage,
+    /// All sizes of the image including `image`, sorted by width
+    pub variants: Vec<CopiedImage>,
+}
 
 pub trait GenerationDirs: Clone + Send + 'static {
     fn get_source_dir(&self) -> PathBuf;
     fn get_generation_dir(&self) -> PathBuf;
+    fn get_config(&self) -> &SiteConfig;
+    fn get_cache(&self) -> &BuildCache;
+    /// Loads the configuration again, since `nr_cms.toml` may have changed since it was loaded.
+    /// Keeps the previous configuration if the file is invalid.
+    fn reload_config(&mut self) -> Result<(), String> {
+        Ok(())
+    }
     fn in_source(&self, path: &str) -> std::path::PathBuf {
         let mut pathbuf = std::path::PathBuf::new();
         pathbuf.push(self.get_source_dir());
@@ -19,6 +52,9 @@ pub trait GenerationDirs: Clone + Send + 'static {
         pathbuf.push(path);
         pathbuf
     }
+    fn in_cache(&self, path: &str) -> std::path::PathBuf {
+        self.in_gen(CACHE_DIR).join(path)
+    }
     fn copy_asset(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
         let target = self.in_gen(path);
         let source = self.in_source(path);
@@ -34,11 +70,105 @@ pub trait GenerationDirs: Clone + Send + 'static {
         std::fs::copy(source, target)?;
         Ok(())
     }
-    fn copy_asset_img(&self, path: &str, size: u32) -> Result<(), Box<dyn std::error::Error>> {
-        let target = self.in_gen(path);
+    /// Name of a processed image in the cache, unique for the source and the settings used.
+    fn img_cache_name(&self, hash: u64, size: u32) -> String {
+        let images = &self.get_config().images;
+        format!(
+            "images/{hash:016x}-{size}-{:?}-{:?}-q{}",
+            images.filter, images.format, images.quality
+        )
+    }
+    /// Copies a resized image into the generation directory. Its path there
+    /// has a different extension if the image was converted to another format.
+    fn copy_asset_img(
+        &self,
+        path: &str,
+        size: u32,
+    ) -> Result<CopiedImage, Box<dyn std::error::Error>> {
+        self.copy_asset_img_variant(path, size, "")
+    }
+    /// Like `copy_asset_img`, but appends `suffix` to the file name.
+    fn copy_asset_img_variant(
+        &self,
+        path: &str,
+        size: u32,
+        suffix: &str,
+    ) -> Result<CopiedImage, Box<dyn std::error::Error>> {
         let source = self.in_source(path);
-        img_handling::resize_image(&source, &target, size)?;
-        Ok(())
+        let images = &self.get_config().images;
+        let hash = self.get_cache().track(&source)?;
+        let format = img_handling::output_format(&source, images.format);
+        let extension = format.extensions_str()[0];
+        let path = std::path::Path::new(path);
+        let path_extension = match image::ImageFormat::from_path(path) {
+            Ok(x) if x == format => path.extension().unwrap_or_default().to_string_lossy(),
+            _ => extension.into(),
+        };
+        let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let path = path.with_file_name(format!("{file_stem}{suffix}.{path_extension}"));
+        let path = path.to_string_lossy().to_string();
+        let target = self.in_gen(&path);
+        let cached = self.in_cache(&format!("{}.{extension}", self.img_cache_name(hash, size)));
+        build_cache::cached_file(&cached, |x| {
+            img_handling::resize_image(&source, x, size, images)
+        })?;
+        let target_parent = target.parent().ok_or(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "cannot find parent",
+        ))?;
+        std::fs::create_dir_all(target_parent)?;
+        std::fs::copy(cached, &target)?;
+        let (width, height) = image::image_dimensions(target)?;
+        Ok(CopiedImage {
+            path,
+            width,
+            height,
+        })
+    }
+    /// Copies an image together with smaller and larger variants for a `srcset`.
+    /// Without a size the image is copied unchanged and only smaller variants are added.
+    fn copy_asset_img_srcset(
+        &self,
+        path: &str,
+        size: Option<u32>,
+    ) -> Result<ResponsiveImage, Box<dyn std::error::Error>> {
+        let image = match size {
+            Some(size) => self.copy_asset_img(path, size)?,
+            None => {
+                self.copy_asset(path)?;
+                let (width, height) = image::image_dimensions(self.in_gen(path))?;
+                CopiedImage {
+                    path: path.to_string(),
+                    width,
+                    height,
+                }
+            }
+        };
+        let (original_width, original_height) = image::image_dimensions(self.in_source(path))?;
+        let original_size = original_width.max(original_height);
+        let display_size = image.width.max(image.height);
+        let mut variants = vec![image.clone()];
+        for variant in [display_size / 4, display_size / 2, display_size * 2] {
+            if variant < MIN_SRCSET_SIZE || variant >= original_size {
+                continue;
+            }
+            variants.push(self.copy_asset_img_variant(path, variant, &format!("-{variant}"))?);
+        }
+        variants.sort_by_key(|x| x.width);
+        variants.dedup_by_key(|x| x.width);
+        Ok(ResponsiveImage { image, variants })
+    }
+    /// Resizes an image and encodes it as a data URL, so that it can be inlined.
+    fn inline_img(&self, path: &str, size: u32) -> Result<String, Box<dyn std::error::Error>> {
+        let source = self.in_source(path);
+        let hash = self.get_cache().track(&source)?;
+        let images = &self.get_config().images;
+        let cached = self.in_cache(&format!("{}.b64", self.img_cache_name(hash, size)));
+        build_cache::cached_file(&cached, |x| {
+            let url = img_handling::get_img_as_b64_url(&source, size, images)?;
+            Ok(std::fs::write(x, url)?)
+        })?;
+        Ok(std::fs::read_to_string(cached)?)
     }
 }
 
@@ -46,6 +176,10 @@ pub trait GenerationDirs: Clone + Send + 'static {
 pub struct StandardGenerationDirs {
     source_dir: std::path::PathBuf,
     generation_dir: std::path::PathBuf,
+    config: SiteConfig,
+    /// The arguments that override settings of the configuration whenever it is loaded
+    args: ProgramArgs,
+    cache: BuildCache,
 }
 
 impl GenerationDirs for StandardGenerationDirs {
@@ -56,29 +190,70 @@ impl GenerationDirs for StandardGenerationDirs {
     fn get_generation_dir(&self) -> PathBuf {
         self.generation_dir.to_path_buf()
     }
-}
 
-impl From<ProgramArgs> for StandardGenerationDirs {
-    fn from(value: ProgramArgs) -> Self {
-        StandardGenerationDirs {
-            source_dir: PathBuf::from(value.source_dir),
-            generation_dir: PathBuf::from(value.generation_dir),
+    fn get_config(&self) -> &SiteConfig {
+        &self.config
+    }
+
+    fn get_cache(&self) -> &BuildCache {
+        &self.cache
+    }
+
+    fn reload_config(&mut self) -> Result<(), String> {
+        let config = SiteConfig::load(&self.source_dir)?.apply_args(&self.args)?;
+        if config != self.config {
+            log::info!("Reloaded {CONFIG_FILE}");
+            self.config = config;
         }
+        Ok(())
+    }
+}
+
+impl TryFrom<ProgramArgs> for StandardGenerationDirs {
+    type Error = String;
+
+    fn try_from(value: ProgramArgs) -> Result<Self, Self::Error> {
+        let source_dir = PathBuf::from(&value.source_dir);
+        let config = SiteConfig::load(&source_dir)?.apply_args(&value)?;
+        Ok(StandardGenerationDirs {
+            config,
+            source_dir,
+            generation_dir: PathBuf::from(&value.generation_dir),
+            args: value,
+            cache: Default::default(),
+        })
     }
 }
 
+#[cfg(test)]
 pub struct TempGenerationDirs {
     tmp_dir: Option<tempfile::TempDir>,
+    config: SiteConfig,
+    cache: BuildCache,
+}
+
+#[cfg(test)]
+impl TempGenerationDirs {
+    pub fn with_config(config: SiteConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
 }
 
+#[cfg(test)]
 impl Clone for TempGenerationDirs {
     fn clone(&self) -> Self {
         Self {
             tmp_dir: tempfile::tempdir().ok(),
+            config: self.config.clone(),
+            cache: self.cache.clone(),
         }
     }
 }
 
+#[cfg(test)]
 impl GenerationDirs for TempGenerationDirs {
     fn get_source_dir(&self) -> PathBuf {
         PathBuf::from("sample/")
@@ -90,12 +265,23 @@ impl GenerationDirs for TempGenerationDirs {
             .map(|x| x.path().to_path_buf())
             .unwrap_or(PathBuf::from("gen_tmp/"))
     }
+
+    fn get_config(&self) -> &SiteConfig {
+        &self.config
+    }
+
+    fn get_cache(&self) -> &BuildCache {
+        &self.cache
+    }
 }
 
+#[cfg(test)]
 impl Default for TempGenerationDirs {
     fn default() -> Self {
         Self {
             tmp_dir: tempfile::tempdir().ok(),
+            config: Default::default(),
+            cache: Default::default(),
         }
     }
 }
@@ -103,6 +289,7 @@ impl Default for TempGenerationDirs {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::site_config::{ImageConfig, ImageEncoding, OutputMode};
     use std::path::PathBuf;
     #[test]
     fn test_program_args_conversion() {
@@ -111,11 +298,63 @@ mod test {
             generation_dir: "second/".to_string(),
             max_log_level: Default::default(),
             watch: Default::default(),
+            output_mode: Some(OutputMode::MultiFile),
+            drafts: true,
+            base_url: Default::default(),
+            command: Default::default(),
         };
-        let generation_dirs = StandardGenerationDirs::from(program_args);
+        let generation_dirs = StandardGenerationDirs::try_from(program_args).unwrap();
         assert_eq!("first/", generation_dirs.source_dir.to_str().unwrap());
         assert_eq!("second/", generation_dirs.generation_dir.to_str().unwrap());
+        assert_eq!(
+            generation_dirs.get_config().output_mode,
+            OutputMode::MultiFile
+        );
+        assert!(generation_dirs.get_config().drafts);
+
+        let program_args = ProgramArgs {
+            source_dir: "sample/".to_string(),
+            base_url: Some("example.com".to_string()),
+            ..Default::default()
+        };
+        let error = StandardGenerationDirs::try_from(program_args)
+            .err()
+            .unwrap();
+        assert!(error.contains("base_url"));
+    }
+
+    #[test]
+    fn test_reload_config() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let config_file = source_dir.path().join(CONFIG_FILE);
+        std::fs::write(&config_file, "debounce_ms = 100").unwrap();
+        let program_args = ProgramArgs {
+            source_dir: source_dir.path().to_string_lossy().to_string(),
+            output_mode: Some(OutputMode::MultiFile),
+            ..Default::default()
+        };
+        let mut generation_dirs = StandardGenerationDirs::try_from(program_args).unwrap();
+        assert_eq!(generation_dirs.get_config().debounce_ms, 100);
+
+        std::fs::write(
+            &config_file,
+            "debounce_ms = 300\noutput_mode = \"single-file\"",
+        )
+        .unwrap();
+        generation_dirs.reload_config().unwrap();
+        assert_eq!(generation_dirs.get_config().debounce_ms, 300);
+        assert_eq!(
+            generation_dirs.get_config().output_mode,
+            OutputMode::MultiFile,
+            "arguments still override the configuration"
+        );
+
+        std::fs::write(&config_file, "debounce_ms = \"soon\"").unwrap();
+        let error = generation_dirs.reload_config().unwrap_err();
+        assert!(error.contains(CONFIG_FILE));
+        assert_eq!(generation_dirs.get_config().debounce_ms, 300);
     }
+
     #[test]
     fn test_path_finding() {
         let generation_dirs = TempGenerationDirs::default();
@@ -131,10 +370,65 @@ mod test {
     #[test]
     fn test_copy_img_resize() {
         let generation_dirs = TempGenerationDirs::default();
+        let copied = generation_dirs.copy_asset_img("sample.jpg", 200).unwrap();
+        assert_eq!(copied.path, "sample.jpg");
+        assert_eq!(copied.width.max(copied.height), 200);
+        assert!(generation_dirs.in_gen("sample.jpg").exists());
+        let cached = std::fs::read_dir(generation_dirs.in_cache("images"))
+            .unwrap()
+            .count();
+        assert_eq!(cached, 1);
+        std::fs::remove_file(generation_dirs.in_gen("sample.jpg")).unwrap();
         generation_dirs.copy_asset_img("sample.jpg", 200).unwrap();
         assert!(generation_dirs.in_gen("sample.jpg").exists());
     }
     #[test]
+    fn test_inline_img() {
+        let generation_dirs = TempGenerationDirs::default();
+        let url = generation_dirs.inline_img("sample.jpg", 10).unwrap();
+        assert!(url.starts_with("data:image/jpeg;base64,"));
+        assert_eq!(generation_dirs.inline_img("sample.jpg", 10).unwrap(), url);
+        assert!(generation_dirs.in_cache("images").exists());
+        assert!(generation_dirs.inline_img("no_exist.jpg", 10).is_err());
+    }
+    #[test]
+    fn test_copy_img_convert() {
+        let generation_dirs = TempGenerationDirs::with_config(SiteConfig {
+            images: ImageConfig {
+                format: ImageEncoding::Png,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let copied = generation_dirs.copy_asset_img("sample.jpg", 20).unwrap();
+        assert_eq!(copied.path, "sample.png");
+        let img = image::open(generation_dirs.in_gen("sample.png")).unwrap();
+        assert_eq!(img.width().max(img.height()), 20);
+    }
+    #[test]
+    fn test_copy_img_srcset() {
+        let generation_dirs = TempGenerationDirs::default();
+        let responsive = generation_dirs
+            .copy_asset_img_srcset("sample.jpg", Some(200))
+            .unwrap();
+        assert_eq!(responsive.image.path, "sample.jpg");
+        let variants = &responsive.variants;
+        let paths = variants.iter().map(|x| x.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, ["sample-100.jpg", "sample.jpg", "sample-400.jpg"]);
+        assert!(variants.windows(2).all(|x| x[0].width < x[1].width));
+        for variant in variants {
+            assert!(generation_dirs.in_gen(&variant.path).exists());
+        }
+
+        let responsive = generation_dirs
+            .copy_asset_img_srcset("sample.jpg", None)
+            .unwrap();
+        let original = image::image_dimensions("sample/sample.jpg").unwrap();
+        assert_eq!(responsive.image.path, "sample.jpg");
+        assert_eq!(responsive.variants.last(), Some(&responsive.image));
+        assert_eq!((responsive.image.width, responsive.image.height), original);
+    }
+    #[test]
     fn test_copy_asset() {
         let generation_dirs = TempGenerationDirs::default();
         generation_dirs.copy_asset("sample.jpg").unwrap();
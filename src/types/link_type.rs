@@ -1,4 +1,135 @@
-#[derive(Eq, PartialEq, Hash, Clone)]
+use std::str::FromStr;
+
+/// The kind of a link in `{{Links|Type:target}}`, which decides how `target` becomes a URL.
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
 pub enum LinkType {
     Github,
+    GitLab,
+    Codeberg,
+    LinkedIn,
+    /// Target is either `@user@instance` or the URL of the profile
+    Mastodon,
+    Email,
+    Bluesky,
+    StackOverflow,
+    Orcid,
+    /// Target is the URL of the feed
+    Rss,
+    /// Target is the URL, `icon` is an image relative to the source directory
+    Custom {
+        label: String,
+        icon: Option<String>,
+    },
+}
+
+/// The names of the built-in link types, as written in `{{Links|...}}`.
+pub const LINK_TYPE_NAMES: &[&str] = &[
+    "Github",
+    "GitLab",
+    "Codeberg",
+    "LinkedIn",
+    "Mastodon",
+    "Email",
+    "Bluesky",
+    "StackOverflow",
+    "ORCID",
+    "RSS",
+    "Custom",
+];
+
+impl FromStr for LinkType {
+    type Err = ();
+
+    /// Parses the built-in link types, `Custom` needs a label and is handled by the parser.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "Github" | "GitHub" => Ok(LinkType::Github),
+            "GitLab" => Ok(LinkType::GitLab),
+            "Codeberg" => Ok(LinkType::Codeberg),
+            "LinkedIn" => Ok(LinkType::LinkedIn),
+            "Mastodon" => Ok(LinkType::Mastodon),
+            "Email" => Ok(LinkType::Email),
+            "Bluesky" => Ok(LinkType::Bluesky),
+            "StackOverflow" => Ok(LinkType::StackOverflow),
+            "ORCID" => Ok(LinkType::Orcid),
+            "RSS" => Ok(LinkType::Rss),
+            _ => Err(()),
+        }
+    }
+}
+
+impl LinkType {
+    /// The text shown next to the icon.
+    pub fn label(&self) -> &str {
+        match self {
+            LinkType::Github => "Github",
+            LinkType::GitLab => "GitLab",
+            LinkType::Codeberg => "Codeberg",
+            LinkType::LinkedIn => "LinkedIn",
+            LinkType::Mastodon => "Mastodon",
+            LinkType::Email => "Email",
+            LinkType::Bluesky => "Bluesky",
+            LinkType::StackOverflow => "Stack Overflow",
+            LinkType::Orcid => "ORCID",
+            LinkType::Rss => "RSS",
+            LinkType::Custom { label, .. } => label,
+        }
+    }
+
+    pub fn url(&self, target: &str) -> String {
+        match self {
+            LinkType::Github => format!("https://github.com/{target}/"),
+            LinkType::GitLab => format!("https://gitlab.com/{target}"),
+            LinkType::Codeberg => format!("https://codeberg.org/{target}"),
+            LinkType::LinkedIn => format!("https://www.linkedin.com/in/{target}/"),
+            LinkType::Mastodon => match target.strip_prefix('@').and_then(|x| x.split_once('@')) {
+                Some((user, instance)) => format!("https://{instance}/@{user}"),
+                None => target.to_string(),
+            },
+            LinkType::Email => format!("mailto:{target}"),
+            LinkType::Bluesky => format!("https://bsky.app/profile/{target}"),
+            LinkType::StackOverflow => format!("https://stackoverflow.com/users/{target}"),
+            LinkType::Orcid => format!("https://orcid.org/{target}"),
+            LinkType::Rss | LinkType::Custom { .. } => target.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            LinkType::Github.url("naresh97"),
+            "https://github.com/naresh97/"
+        );
+        assert_eq!(
+            LinkType::Mastodon.url("@naresh@mastodon.social"),
+            "https://mastodon.social/@naresh"
+        );
+        assert_eq!(
+            LinkType::Mastodon.url("https://example.social/@me"),
+            "https://example.social/@me"
+        );
+        assert_eq!(
+            LinkType::Email.url("me@example.com"),
+            "mailto:me@example.com"
+        );
+        let custom = LinkType::Custom {
+            label: "Blog".to_string(),
+            icon: None,
+        };
+        assert_eq!(custom.url("https://example.com"), "https://example.com");
+        assert_eq!(custom.label(), "Blog");
+    }
+
+    #[test]
+    fn test_names() {
+        for name in LINK_TYPE_NAMES.iter().filter(|x| **x != "Custom") {
+            assert!(name.parse::<LinkType>().is_ok(), "{name}");
+        }
+        assert!("Custom".parse::<LinkType>().is_err());
+    }
 }
@@ -1,7 +1,10 @@
 pub mod cms_blog;
+pub mod cms_image;
 pub mod cms_page;
 pub mod cms_site;
+pub mod diagnostic;
 pub mod generation_dirs;
 pub mod link_type;
 pub mod program_args;
+pub mod site_config;
 pub mod template_type;
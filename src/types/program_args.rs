@@ -1,6 +1,8 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-#[derive(Parser, Default)]
+use super::site_config::OutputMode;
+
+#[derive(Parser, Default, Clone)]
 #[command(author,version,about,long_about=None)]
 pub struct ProgramArgs {
     pub source_dir: String,
@@ -10,4 +12,26 @@ pub struct ProgramArgs {
     pub max_log_level: String,
     #[arg(short, long)]
     pub watch: bool,
+    /// Overrides `output_mode` in nr_cms.toml
+    #[arg(short, long, value_enum)]
+    pub output_mode: Option<OutputMode>,
+    /// Overrides `base_url` in nr_cms.toml, e.g. https://example.com
+    #[arg(short, long)]
+    pub base_url: Option<String>,
+    /// Also publish drafts and blog posts dated in the future
+    #[arg(long)]
+    pub drafts: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Serve the generated website locally and reload it on changes
+    Serve {
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+        #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
+        address: String,
+    },
 }
@@ -0,0 +1,368 @@
+use std::path::Path;
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    Locale,
+};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::build_cache::hash_bytes;
+
+use super::program_args::ProgramArgs;
+
+/// Name of the configuration file, looked up in the source directory.
+pub const CONFIG_FILE: &str = "nr_cms.toml";
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputMode {
+    /// Everything is written into a single index.html, pages are selected with `?page=`
+    #[default]
+    SingleFile,
+    /// Every page and blog post is written into its own index.html
+    MultiFile,
+}
+
+/// Filter used when resizing images, from fastest to sharpest.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    #[default]
+    Lanczos3,
+}
+
+/// Format that resized images are encoded in.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageEncoding {
+    /// Keep JPEGs and PNGs as they are. Inlined images without transparency become JPEGs.
+    #[default]
+    Auto,
+    Jpeg,
+    Png,
+    /// Requires nr_cms to be built with the `webp` feature
+    Webp,
+}
+
+/// How images are resized and when they are inlined into the HTML.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageConfig {
+    /// Size in pixels used for images that do not specify one
+    pub default_size: u32,
+    /// JPEG and WebP quality of resized images, from 1 to 100
+    pub quality: u8,
+    /// Images whose base64 encoding is at most this many bytes are inlined
+    pub max_inline_size: usize,
+    pub filter: ResizeFilter,
+    pub format: ImageEncoding,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        ImageConfig {
+            default_size: 200,
+            quality: 70,
+            max_inline_size: 1000,
+            filter: Default::default(),
+            format: Default::default(),
+        }
+    }
+}
+
+/// How the posts of a blog are listed.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlogLayout {
+    /// Every post is shown in full
+    #[default]
+    Full,
+    /// Only the title, date and an excerpt are shown, linking to the full post
+    List,
+}
+
+/// How blogs are listed and split into pages.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlogConfig {
+    /// Number of posts per page of the listing, 0 shows all posts on one page
+    pub posts_per_page: usize,
+    pub layout: BlogLayout,
+    /// Maximum number of characters of an excerpt in the list layout
+    pub excerpt_length: usize,
+}
+
+impl Default for BlogConfig {
+    fn default() -> Self {
+        BlogConfig {
+            posts_per_page: 0,
+            layout: Default::default(),
+            excerpt_length: 200,
+        }
+    }
+}
+
+/// How the dates of blog posts are shown.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateConfig {
+    /// strftime pattern, e.g. `%Y-%m-%d`
+    pub format: String,
+    /// Locale of month and weekday names, e.g. `de_DE`
+    pub locale: String,
+    /// Additionally rewrite dates in the time zone and format of the reader with JavaScript
+    pub localize: bool,
+}
+
+impl Default for DateConfig {
+    fn default() -> Self {
+        DateConfig {
+            format: "%B %-d, %Y".to_string(),
+            locale: "en_US".to_string(),
+            localize: false,
+        }
+    }
+}
+
+impl DateConfig {
+    pub fn locale(&self) -> Locale {
+        Locale::try_from(self.locale.as_str()).unwrap_or(Locale::POSIX)
+    }
+}
+
+/// Options that change how the website is generated.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    pub output_mode: OutputMode,
+    /// Public URL of the website, needed wherever absolute links are required
+    pub base_url: Option<String>,
+    pub author: Option<String>,
+    /// Language of the website, e.g. `en`
+    pub language: Option<String>,
+    /// Name of the page that is shown at the root of the website
+    pub default_page: String,
+    /// IANA time zone, e.g. `Europe/Berlin`, in which dates without an offset are read
+    pub timezone: Tz,
+    /// HTML shown by the `{{NKR-CMS-INFO}}` template
+    pub nr_cms_info: String,
+    pub images: ImageConfig,
+    pub blog: BlogConfig,
+    pub dates: DateConfig,
+    /// How long the watcher waits for further changes before rebuilding, in milliseconds
+    pub debounce_ms: u64,
+    /// Publish drafts and scheduled blog posts, only set with `--drafts`
+    #[serde(skip)]
+    pub drafts: bool,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            output_mode: Default::default(),
+            base_url: None,
+            author: None,
+            language: None,
+            default_page: "home".to_string(),
+            timezone: Tz::UTC,
+            nr_cms_info: "This website was automatically generated with <a href=\"https://github.com/naresh97/nr-cms\">NR-CMS.</a>".to_string(),
+            images: Default::default(),
+            blog: Default::default(),
+            dates: Default::default(),
+            debounce_ms: 200,
+            drafts: false,
+        }
+    }
+}
+
+impl SiteConfig {
+    /// Loads `nr_cms.toml` from the source directory, or the defaults if there is none.
+    pub fn load(source_dir: &Path) -> Result<SiteConfig, String> {
+        let path = source_dir.join(CONFIG_FILE);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) => return Err(format!("could not read {}: {e}", path.display())),
+        };
+        Self::parse(&content).map_err(|e| format!("invalid {}: {e}", path.display()))
+    }
+
+    fn parse(content: &str) -> Result<SiteConfig, String> {
+        let config: SiteConfig = toml::from_str(content).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(base_url) = &self.base_url {
+            if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+                return Err(format!(
+                    "`base_url` must start with http:// or https://, found `{base_url}`"
+                ));
+            }
+        }
+        if self.default_page.trim().is_empty() {
+            return Err("`default_page` must not be empty".to_string());
+        }
+        if !(1..=100).contains(&self.images.quality) {
+            return Err(format!(
+                "`images.quality` must be between 1 and 100, found {}",
+                self.images.quality
+            ));
+        }
+        if self.images.default_size == 0 {
+            return Err("`images.default_size` must be greater than 0".to_string());
+        }
+        if self.blog.layout == BlogLayout::List && self.blog.excerpt_length == 0 {
+            return Err("`blog.excerpt_length` must be greater than 0".to_string());
+        }
+        if StrftimeItems::new(&self.dates.format).any(|x| matches!(x, Item::Error)) {
+            return Err(format!(
+                "`dates.format` is not a valid strftime pattern: `{}`",
+                self.dates.format
+            ));
+        }
+        if Locale::try_from(self.dates.locale.as_str()).is_err() {
+            return Err(format!(
+                "`dates.locale` must be a locale like `en_US` or `de_DE`, found `{}`",
+                self.dates.locale
+            ));
+        }
+        if self.images.format == ImageEncoding::Webp && !cfg!(feature = "webp") {
+            return Err(
+                "`images.format = \"webp\"` requires nr_cms to be built with `--features webp`"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Lets the command line arguments override the configuration file.
+    pub fn apply_args(mut self, args: &ProgramArgs) -> Result<SiteConfig, String> {
+        if let Some(output_mode) = args.output_mode {
+            self.output_mode = output_mode;
+        }
+        if let Some(base_url) = &args.base_url {
+            self.base_url = Some(base_url.clone());
+        }
+        self.drafts |= args.drafts;
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Changes whenever any setting changes, so that cached results can be invalidated.
+    pub fn fingerprint(&self) -> u64 {
+        hash_bytes(format!("{self:?}").as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = SiteConfig::parse(
+            r#"
+            output_mode = "multi-file"
+            base_url = "https://example.com"
+            language = "en"
+            timezone = "Europe/Berlin"
+
+            [images]
+            quality = 90
+            filter = "catmull-rom"
+            format = "png"
+
+            [blog]
+            posts_per_page = 10
+            layout = "list"
+
+            [dates]
+            format = "%-d. %B %Y"
+            locale = "de_DE"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.output_mode, OutputMode::MultiFile);
+        assert_eq!(config.base_url.as_deref(), Some("https://example.com"));
+        assert_eq!(config.language.as_deref(), Some("en"));
+        assert_eq!(config.images.quality, 90);
+        assert_eq!(config.images.default_size, 200);
+        assert_eq!(config.images.filter, ResizeFilter::CatmullRom);
+        assert_eq!(config.images.format, ImageEncoding::Png);
+        assert_eq!(config.blog.posts_per_page, 10);
+        assert_eq!(config.blog.layout, BlogLayout::List);
+        assert_eq!(config.blog.excerpt_length, 200);
+        assert_eq!(config.default_page, "home");
+        assert_eq!(config.timezone, chrono_tz::Europe::Berlin);
+        assert_eq!(config.dates.locale(), Locale::de_DE);
+        assert!(!config.dates.localize);
+        assert_eq!(SiteConfig::parse("").unwrap(), SiteConfig::default());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = SiteConfig::parse("base_ur = \"https://example.com\"").unwrap_err();
+        assert!(error.contains("unknown field `base_ur`"));
+        let error = SiteConfig::parse("output_mode = \"many-files\"").unwrap_err();
+        assert!(error.contains("unknown variant `many-files`"));
+        let error = SiteConfig::parse("[dates]\nformat = \"%Y-%Q\"").unwrap_err();
+        assert!(error.contains("not a valid strftime pattern"));
+        let error = SiteConfig::parse("[dates]\nlocale = \"klingon\"").unwrap_err();
+        assert!(error.contains("must be a locale"));
+        let error = SiteConfig::parse("timezone = \"Mars/Olympus\"").unwrap_err();
+        assert!(error.contains("Mars/Olympus"));
+        let error = SiteConfig::parse("base_url = \"example.com\"").unwrap_err();
+        assert!(error.contains("must start with http"));
+        let error = SiteConfig::parse("[images]\nquality = 0").unwrap_err();
+        assert!(error.contains("between 1 and 100"));
+        let error = SiteConfig::parse("[images]\nfilter = \"bicubic\"").unwrap_err();
+        assert!(error.contains("unknown variant `bicubic`"));
+        let error = SiteConfig::parse("[blog]\nlayout = \"list\"\nexcerpt_length = 0").unwrap_err();
+        assert!(error.contains("`blog.excerpt_length`"));
+        let webp = SiteConfig::parse("[images]\nformat = \"webp\"");
+        assert_eq!(webp.is_ok(), cfg!(feature = "webp"));
+    }
+
+    #[test]
+    fn test_load() {
+        let config = SiteConfig::load(Path::new("sample/")).unwrap();
+        assert_eq!(config.language.as_deref(), Some("en"));
+        let tmp_dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            SiteConfig::load(tmp_dir.path()).unwrap(),
+            SiteConfig::default()
+        );
+        std::fs::write(tmp_dir.path().join(CONFIG_FILE), "author = 1").unwrap();
+        let error = SiteConfig::load(tmp_dir.path()).unwrap_err();
+        assert!(error.contains(CONFIG_FILE));
+    }
+
+    #[test]
+    fn test_apply_args() {
+        let args = ProgramArgs {
+            output_mode: Some(OutputMode::MultiFile),
+            base_url: Some("https://example.org".to_string()),
+            drafts: true,
+            ..Default::default()
+        };
+        let config = SiteConfig {
+            base_url: Some("https://example.com".to_string()),
+            ..Default::default()
+        };
+        let config = config.apply_args(&args).unwrap();
+        assert_eq!(config.output_mode, OutputMode::MultiFile);
+        assert_eq!(config.base_url.as_deref(), Some("https://example.org"));
+        assert!(config.drafts);
+        let config = SiteConfig::default()
+            .apply_args(&ProgramArgs::default())
+            .unwrap();
+        assert_eq!(config, SiteConfig::default());
+    }
+}
@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use chrono::{DateTime, Utc};
 
-use super::{cms_blog::CMSBlog, link_type::LinkType};
+use super::{cms_blog::CMSBlog, cms_image::CMSImage, link_type::LinkType};
 
 #[derive(Clone)]
 pub enum TemplateType {
@@ -13,19 +13,16 @@ pub enum TemplateType {
         content: String,
     },
     Links {
-        links: HashMap<LinkType, String>,
+        /// Link types with their targets, in the order they were written
+        links: Vec<(LinkType, String)>,
     },
     Navbar {
         paths: Vec<String>,
     },
     NRCMSInfo {
-        text: &'static str,
-    },
-    Image {
-        url: String,
-        copy_asset: bool,
-        size: Option<u32>,
+        text: String,
     },
+    Image(CMSImage),
     Name {
         name: String,
     },
@@ -35,7 +32,20 @@ pub enum TemplateType {
     Blog(CMSBlog),
     Code {
         code: String,
+        language: Option<String>,
+    },
+    Markdown {
+        content: String,
+        /// The local images of the Markdown, by their URL as written
+        images: BTreeMap<String, CMSImage>,
+    },
+    Html {
+        content: String,
     },
+    Tags {
+        tags: Vec<String>,
+    },
+    Draft,
 }
 
 impl TemplateType {
@@ -51,7 +61,7 @@ impl TemplateType {
         }
         None
     }
-    pub fn get_links(&self) -> Option<&HashMap<LinkType, String>> {
+    pub fn get_links(&self) -> Option<&Vec<(LinkType, String)>> {
         if let TemplateType::Links { links } = self {
             return Some(links);
         }
@@ -69,14 +79,9 @@ impl TemplateType {
         }
         None
     }
-    pub fn get_image(&self) -> Option<(&String, &bool, &Option<u32>)> {
-        if let TemplateType::Image {
-            url,
-            copy_asset,
-            size,
-        } = self
-        {
-            return Some((url, copy_asset, size));
+    pub fn get_image(&self) -> Option<&CMSImage> {
+        if let TemplateType::Image(image) = self {
+            return Some(image);
         }
         None
     }
@@ -98,9 +103,27 @@ impl TemplateType {
         }
         None
     }
-    pub fn get_code(&self) -> Option<&String> {
-        if let TemplateType::Code { code } = self {
-            return Some(code);
+    pub fn get_code(&self) -> Option<(&String, Option<&String>)> {
+        if let TemplateType::Code { code, language } = self {
+            return Some((code, language.as_ref()));
+        }
+        None
+    }
+    pub fn get_markdown(&self) -> Option<&String> {
+        if let TemplateType::Markdown { content, .. } = self {
+            return Some(content);
+        }
+        None
+    }
+    pub fn get_html(&self) -> Option<&String> {
+        if let TemplateType::Html { content } = self {
+            return Some(content);
+        }
+        None
+    }
+    pub fn get_tags(&self) -> Option<&Vec<String>> {
+        if let TemplateType::Tags { tags } = self {
+            return Some(tags);
         }
         None
     }
@@ -109,13 +132,12 @@ impl TemplateType {
 pub trait TemplateTypeVector {
     fn get_title(&self) -> Option<&String>;
     fn get_navbar(&self) -> Option<&Vec<String>>;
-    fn get_paragraphs(&self) -> Vec<&String>;
-    fn get_links(&self) -> Option<&HashMap<LinkType, String>>;
+    fn get_links(&self) -> Option<&Vec<(LinkType, String)>>;
     fn get_nr_cms_info(&self) -> Option<&str>;
-    fn get_image(&self) -> Option<(&String, &bool, &Option<u32>)>;
     fn get_date(&self) -> Option<&DateTime<Utc>>;
     fn get_blog(&self) -> Option<&CMSBlog>;
-    fn get_code(&self) -> Option<&String>;
+    fn get_tags(&self) -> Option<&Vec<String>>;
+    fn is_draft(&self) -> bool;
 }
 
 impl TemplateTypeVector for Vec<TemplateType> {
@@ -127,11 +149,7 @@ impl TemplateTypeVector for Vec<TemplateType> {
         self.iter().find_map(|x| x.get_navbar())
     }
 
-    fn get_paragraphs(&self) -> Vec<&String> {
-        self.iter().filter_map(|x| x.get_paragraph()).collect()
-    }
-
-    fn get_links(&self) -> Option<&HashMap<LinkType, String>> {
+    fn get_links(&self) -> Option<&Vec<(LinkType, String)>> {
         self.iter().find_map(|x| x.get_links())
     }
 
@@ -139,10 +157,6 @@ impl TemplateTypeVector for Vec<TemplateType> {
         self.iter().find_map(|x| x.get_nr_cms_info())
     }
 
-    fn get_image(&self) -> Option<(&String, &bool, &Option<u32>)> {
-        self.iter().find_map(|x| x.get_image())
-    }
-
     fn get_date(&self) -> Option<&DateTime<Utc>> {
         self.iter().find_map(|x| x.get_date())
     }
@@ -151,8 +165,12 @@ impl TemplateTypeVector for Vec<TemplateType> {
         self.iter().find_map(|x| x.get_blog())
     }
 
-    fn get_code(&self) -> Option<&String> {
-        self.iter().find_map(|x| x.get_code())
+    fn get_tags(&self) -> Option<&Vec<String>> {
+        self.iter().find_map(|x| x.get_tags())
+    }
+
+    fn is_draft(&self) -> bool {
+        self.iter().any(|x| matches!(x, TemplateType::Draft))
     }
 }
 
@@ -186,11 +204,11 @@ mod test {
     #[test]
     fn test_get_links() {
         let a = TemplateType::Links {
-            links: HashMap::from([(LinkType::Github, "test".to_string())]),
+            links: Vec::from([(LinkType::Github, "test".to_string())]),
         };
         assert_eq!(
-            a.get_links().unwrap().get(&LinkType::Github).unwrap(),
-            "test"
+            a.get_links().unwrap()[0],
+            (LinkType::Github, "test".to_string())
         );
         let b = TemplateType::Name {
             name: "abc".to_string(),
@@ -200,7 +218,9 @@ mod test {
 
     #[test]
     fn test_get_nr_cms_info() {
-        let a = TemplateType::NRCMSInfo { text: "test" };
+        let a = TemplateType::NRCMSInfo {
+            text: "test".to_string(),
+        };
         assert_eq!(a.get_nr_cms_info().unwrap(), "test");
         let b = TemplateType::Name {
             name: "abc".to_string(),
@@ -210,12 +230,16 @@ mod test {
 
     #[test]
     fn test_get_image() {
-        let a = TemplateType::Image {
+        let a = TemplateType::Image(CMSImage {
             url: "test".to_string(),
             copy_asset: false,
             size: Some(10),
-        };
-        assert_eq!(a.get_image().unwrap().0, "test");
+            alt: String::new(),
+            caption: None,
+            link: None,
+            align: None,
+        });
+        assert_eq!(a.get_image().unwrap().url, "test");
         let b = TemplateType::Name {
             name: "abc".to_string(),
         };
@@ -245,4 +269,30 @@ mod test {
         };
         assert!(b.get_navbar().is_none());
     }
+
+    #[test]
+    fn test_get_markdown() {
+        let a = TemplateType::Markdown {
+            content: "test".to_string(),
+            images: Default::default(),
+        };
+        assert_eq!(a.get_markdown().unwrap(), "test");
+        let b = TemplateType::Title {
+            title: "test".to_string(),
+        };
+        assert!(b.get_markdown().is_none());
+    }
+
+    #[test]
+    fn test_get_html() {
+        let a = TemplateType::Html {
+            content: "<br/>".to_string(),
+        };
+        assert_eq!(a.get_html().unwrap(), "<br/>");
+        let b = TemplateType::Markdown {
+            content: "test".to_string(),
+            images: Default::default(),
+        };
+        assert!(b.get_html().is_none());
+    }
 }
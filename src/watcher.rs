@@ -1,12 +1,59 @@
-use std::sync::mpsc::Receiver;
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
 
 use notify::{RecursiveMode, Watcher};
 
-use crate::{
-    generation::generate_website::generate_website, types::generation_dirs::GenerationDirs,
-};
+use crate::{generation::generate_website::build_website, types::generation_dirs::GenerationDirs};
+
+/// File names that editors use for temporary files while saving.
+const TEMP_FILE_SUFFIXES: &[&str] = &[".swp", ".swx", "~"];
+const TEMP_FILE_PREFIXES: &[&str] = &[".#"];
+
+enum WatchMessage {
+    Changed,
+    Cancel,
+}
+
+/// Why [`wait_for_changes`] returned.
+#[derive(PartialEq, Debug)]
+enum Wake {
+    Changed,
+    /// A scheduled blog post is due
+    Due,
+    Stop,
+}
 
-fn watch_event(event: notify::Event, generation_dirs: impl GenerationDirs) {
+/// Makes a path absolute without requiring it to exist, so that removed files can be compared.
+fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => normalize_path(parent).join(name),
+        _ => std::env::current_dir().unwrap_or_default().join(path),
+    }
+}
+
+fn is_temp_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|x| x.to_str()) else {
+        return false;
+    };
+    TEMP_FILE_SUFFIXES.iter().any(|x| name.ends_with(x))
+        || TEMP_FILE_PREFIXES.iter().any(|x| name.starts_with(x))
+}
+
+/// Whether a changed path can be ignored. `generation_dir` has to be normalized,
+/// the website would otherwise be rebuilt whenever it is written.
+fn is_ignored(path: &Path, generation_dir: &Path) -> bool {
+    is_temp_file(path) || normalize_path(path).starts_with(generation_dir)
+}
+
+fn is_relevant(event: &notify::Event, generation_dir: &Path) -> bool {
     let handled = match event.kind {
         notify::EventKind::Create(_) => (true, "create"),
         notify::EventKind::Modify(_) => (true, "modify"),
@@ -24,68 +71,146 @@ fn watch_event(event: notify::Event, generation_dirs: impl GenerationDirs) {
     );
     log::trace!("Filesystem event kind: {}", handled.1);
     if !handled.0 {
-        return;
+        return false;
     }
-    log::info!("Filesystem change detected");
-    generate_website(&generation_dirs);
+    event.paths.is_empty() || event.paths.iter().any(|x| !is_ignored(x, generation_dir))
 }
 
 fn watch_error(e: notify::Error) {
     log::error!("Filesystem Watcher Error: {}", e.to_string());
 }
 
+/// Blocks until something changed and no further change followed within `debounce`,
+/// or until `due` is reached. Changes that happen during a rebuild are queued and handled
+/// once it is done.
+fn wait_for_changes(
+    messages: &Receiver<WatchMessage>,
+    debounce: Duration,
+    due: Option<Instant>,
+) -> Wake {
+    let first = match due {
+        Some(due) => messages.recv_timeout(due.saturating_duration_since(Instant::now())),
+        None => messages.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    match first {
+        Ok(WatchMessage::Changed) => (),
+        Err(RecvTimeoutError::Timeout) => return Wake::Due,
+        Ok(WatchMessage::Cancel) | Err(RecvTimeoutError::Disconnected) => return Wake::Stop,
+    }
+    loop {
+        match messages.recv_timeout(debounce) {
+            Ok(WatchMessage::Changed) => continue,
+            Err(RecvTimeoutError::Timeout) => return Wake::Changed,
+            Ok(WatchMessage::Cancel) | Err(RecvTimeoutError::Disconnected) => return Wake::Stop,
+        }
+    }
+}
+
+/// When to rebuild for a post published at `date`, `None` if that is too far away to wait for.
+fn due_instant(date: DateTime<Utc>) -> Option<Instant> {
+    // Rebuilding a moment later makes sure the post is no longer in the future
+    let wait = (date - Utc::now()).to_std().unwrap_or_default() + Duration::from_secs(1);
+    Instant::now().checked_add(wait)
+}
+
+/// Generates the website, then again whenever the source directory changes or a scheduled
+/// blog post becomes due.
 pub fn watch(
-    generation_dirs: impl GenerationDirs,
+    mut generation_dirs: impl GenerationDirs,
     cancellation_token: Option<Receiver<bool>>,
+) -> Result<(), notify::Error> {
+    let build = build_website(&mut generation_dirs);
+    watch_with_callback(
+        generation_dirs,
+        cancellation_token,
+        build.next_publication,
+        |_| {},
+    )
+}
+
+/// Like [`watch`], but without the initial build, which has scheduled `next_publication`.
+/// Hands the result of every rebuild to `on_build`.
+pub fn watch_with_callback(
+    mut generation_dirs: impl GenerationDirs,
+    cancellation_token: Option<Receiver<bool>>,
+    next_publication: Option<DateTime<Utc>>,
+    on_build: impl Fn(Result<(), String>),
 ) -> Result<(), notify::Error> {
     let source_dir = &generation_dirs.get_source_dir();
+    let generation_dir = normalize_path(&generation_dirs.get_generation_dir());
+    let (sender, messages) = std::sync::mpsc::channel();
+    let events = sender.clone();
     let mut watcher = notify::recommended_watcher(move |res| match res {
         Ok(event) => {
-            watch_event(event, generation_dirs.clone());
+            if is_relevant(&event, &generation_dir) {
+                let _ = events.send(WatchMessage::Changed);
+            }
         }
         Err(e) => watch_error(e),
     })?;
     watcher.watch(source_dir, RecursiveMode::Recursive)?;
-    loop {
-        let cancellation_token = cancellation_token.as_ref().and_then(|x| x.recv().ok());
-        if let Some(cancellation_token) = cancellation_token {
-            if cancellation_token {
-                break;
+    if let Some(cancellation_token) = cancellation_token {
+        std::thread::spawn(move || {
+            while let Ok(cancel) = cancellation_token.recv() {
+                if cancel {
+                    let _ = sender.send(WatchMessage::Cancel);
+                    return;
+                }
             }
+        });
+    }
+    let mut due = next_publication.and_then(due_instant);
+    loop {
+        // Read on every change, since `nr_cms.toml` may have changed
+        let debounce = Duration::from_millis(generation_dirs.get_config().debounce_ms);
+        match wait_for_changes(&messages, debounce, due) {
+            Wake::Changed => log::info!("Filesystem change detected"),
+            Wake::Due => log::info!("Publishing scheduled blog posts"),
+            Wake::Stop => break,
         }
+        let build = build_website(&mut generation_dirs);
+        due = build.next_publication.and_then(due_instant);
+        on_build(build.result);
     }
-
+    log::info!("Stopped watching {}", source_dir.display());
     Ok(())
 }
 
 #[cfg(test)]
 mod test {
-    use std::time::Duration;
+    use std::time::Instant;
 
     use crate::types::generation_dirs::TempGenerationDirs;
 
     use super::*;
+
+    fn modify_event(paths: Vec<PathBuf>) -> notify::Event {
+        notify::Event {
+            kind: notify::EventKind::Modify(notify::event::ModifyKind::Data(
+                notify::event::DataChange::Any,
+            )),
+            paths,
+            attrs: Default::default(),
+        }
+    }
+
     #[test]
     fn test_watch_methods() {
-        // Should not panic
-        watch_event(
-            notify::Event {
-                kind: Default::default(),
-                paths: Default::default(),
-                attrs: Default::default(),
-            },
-            TempGenerationDirs::default(),
-        );
-        watch_event(
-            notify::Event {
-                kind: notify::EventKind::Modify(notify::event::ModifyKind::Data(
-                    notify::event::DataChange::Any,
-                )),
-                paths: Default::default(),
-                attrs: Default::default(),
-            },
-            TempGenerationDirs::default(),
-        );
+        let generation_dir = normalize_path(Path::new("sample/gen"));
+        let event = notify::Event {
+            kind: Default::default(),
+            paths: Default::default(),
+            attrs: Default::default(),
+        };
+        assert!(!is_relevant(&event, &generation_dir));
+        assert!(is_relevant(&modify_event(Vec::new()), &generation_dir));
+        let event = modify_event(Vec::from([PathBuf::from("sample/index.cms")]));
+        assert!(is_relevant(&event, &generation_dir));
+        let event = modify_event(Vec::from([
+            PathBuf::from("sample/gen/index.html"),
+            PathBuf::from("sample/.index.cms.swp"),
+        ]));
+        assert!(!is_relevant(&event, &generation_dir));
 
         // Should not panic
         watch_error(notify::Error {
@@ -94,6 +219,82 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_is_ignored() {
+        let generation_dir = normalize_path(Path::new("sample/gen/"));
+        assert!(is_ignored(
+            Path::new("sample/gen/blog/index.html"),
+            &generation_dir
+        ));
+        assert!(is_ignored(Path::new("sample/index.cms~"), &generation_dir));
+        assert!(is_ignored(Path::new("sample/.#index.cms"), &generation_dir));
+        assert!(is_ignored(
+            Path::new("sample/.index.cms.swp"),
+            &generation_dir
+        ));
+        assert!(!is_ignored(Path::new("sample/index.cms"), &generation_dir));
+        assert!(!is_ignored(
+            Path::new("sample/pages/contact.cms"),
+            &generation_dir
+        ));
+        assert!(!is_ignored(
+            Path::new("sample/generated.cms"),
+            &generation_dir
+        ));
+        let absolute = normalize_path(Path::new("sample/index.cms"));
+        assert!(absolute.is_absolute());
+        assert_eq!(absolute, normalize_path(&absolute));
+    }
+
+    #[test]
+    fn test_wait_for_changes() {
+        let (sender, messages) = std::sync::mpsc::channel();
+        for _ in 0..3 {
+            sender.send(WatchMessage::Changed).unwrap();
+        }
+        let start = Instant::now();
+        assert_eq!(
+            wait_for_changes(&messages, Duration::from_millis(50), None),
+            Wake::Changed
+        );
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(messages.try_recv().is_err(), "changes are coalesced");
+
+        sender.send(WatchMessage::Changed).unwrap();
+        sender.send(WatchMessage::Cancel).unwrap();
+        assert_eq!(
+            wait_for_changes(&messages, Duration::from_millis(50), None),
+            Wake::Stop
+        );
+        drop(sender);
+        assert_eq!(
+            wait_for_changes(&messages, Duration::from_millis(50), None),
+            Wake::Stop
+        );
+    }
+
+    #[test]
+    fn test_wait_until_due() {
+        let (sender, messages) = std::sync::mpsc::channel();
+        let due = Instant::now() + Duration::from_millis(50);
+        assert_eq!(
+            wait_for_changes(&messages, Duration::from_millis(50), Some(due)),
+            Wake::Due
+        );
+        assert!(Instant::now() >= due);
+        sender.send(WatchMessage::Changed).unwrap();
+        assert_eq!(
+            wait_for_changes(&messages, Duration::from_millis(10), Some(due)),
+            Wake::Changed,
+            "changes are handled even if a post is overdue"
+        );
+
+        let date = Utc::now() + chrono::Duration::seconds(10);
+        let wait = due_instant(date).unwrap() - Instant::now();
+        assert!(wait > Duration::from_secs(10) && wait <= Duration::from_secs(11));
+        assert!(due_instant(Utc::now() - chrono::Duration::days(1)).is_some());
+    }
+
     #[test]
     fn test_watch() {
         let (tx, rx) = std::sync::mpsc::channel::<bool>();
@@ -103,8 +304,9 @@ mod test {
         tx.send(false).unwrap();
         std::thread::sleep(Duration::from_millis(100));
         assert!(!handle.is_finished());
+        // The initial build runs before the watcher listens for the cancellation
         tx.send(true).unwrap();
-        for _ in 0..10 {
+        for _ in 0..100 {
             if !handle.is_finished() {
                 std::thread::sleep(Duration::from_millis(100));
             } else {